@@ -0,0 +1,166 @@
+//! Instructions per second of CpuCore::tick, running the instruction decoded once per memory
+//! word, against the dispatch it replaced, matching the opcode to its op_ function on every
+//! tick, and of CpuCore::run_block running the translated blocks.
+//!
+//! `cargo bench --bench decoder`
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use oxidate::cpu::Cpu;
+use oxidate::cpu_core::{CpuCore, MEMORY_SIZE, START_ADDRESS};
+use oxidate::instruction::Instruction::{self, *};
+use oxidate::quirks::Platform;
+use std::hint::black_box;
+
+/// Instructions run per iteration.
+const TICKS: u64 = 10_000;
+/// A loop of arithmetic, skips, stores and a draw, none of them waiting.
+const ROM: [u8; 24] = [
+    0x60, 0x00, // 200 LD V0, #00
+    0x70, 0x01, // 202 ADD V0, #01
+    0x81, 0x04, // 204 ADD V1, V0
+    0x82, 0x13, // 206 XOR V2, V1
+    0x83, 0x26, // 208 SHR V3, V2
+    0x30, 0x10, // 20A SE V0, #10
+    0x12, 0x02, // 20C JP #202
+    0xA3, 0x00, // 20E LD I, #300
+    0xF3, 0x55, // 210 LD [I], V3
+    0xF1, 0x33, // 212 LD B, V1
+    0xD0, 0x15, // 214 DRW V0, V1, 5
+    0x12, 0x00, // 216 JP #200
+];
+
+fn cpu() -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_rom_at(&ROM, START_ADDRESS).unwrap();
+    cpu
+}
+
+/// The tick before the decoded instructions: fetch, then dispatch and run every time.
+fn tick_dispatching(core: &mut CpuCore, cpu: &mut Cpu) {
+    cpu.draw_flag = false;
+    let pc = usize::from(cpu.pc);
+    let opcode = u16::from_be_bytes([cpu.mem[pc], cpu.mem[pc + 1]]);
+    dispatch(core, cpu, opcode);
+    if !cpu.wait_for_key {
+        cpu.pc = (cpu.pc + 2) % MEMORY_SIZE as u16;
+    }
+}
+
+/// The match of the opcode the tick went through before the decoded instructions, straight
+/// to the op_ functions. CpuCore::handler of a constant is the op_ function itself.
+fn dispatch(core: &mut CpuCore, cpu: &mut Cpu, opcode: u16) {
+    let chip8x = core.platform == Platform::Chip8x;
+    let mega = cpu.mega.as_ref().is_some_and(|m| m.enabled);
+    let op = |instruction: Instruction| CpuCore::handler(instruction);
+
+    let handler = match opcode {
+        0x0010 | 0x0011 if cpu.mega.is_some() => op(MegaMode),
+        0x00e0 if mega => op(MegaCls),
+        0x00e0 => op(Cls),
+        0x00d4 => op(MachineRet),
+        0x0230 if cpu.hires() => op(Cls),
+        0x00ee => op(Ret),
+        0x02a0 if chip8x => op(Background),
+        0x0100..=0x01FF if mega => op(LdILong),
+        0x0200..=0x02FF if mega => op(Palette),
+        0x0300..=0x03FF if mega => op(SpriteWidth),
+        0x0400..=0x04FF if mega => op(SpriteHeight),
+        0x0500..=0x05FF if mega => op(Alpha),
+        0x0600..=0x060F if mega => op(PlaySample),
+        0x0700 if mega => op(StopSample),
+        0x0800..=0x080F if mega => op(Blend),
+        0x0900..=0x09FF if mega => op(Collision),
+        0x0000..=0x0FFF => op(Sys),
+        0x1000..=0x1FFF => op(Jp),
+        0x2000..=0x2FFF => op(Call),
+        0x3000..=0x3FFF => op(SeByte),
+        0x4000..=0x4FFF => op(SneByte),
+        code if code & 0xF00F == 0x5000 => op(SeReg),
+        code if chip8x && code & 0xF00F == 0x5001 => op(AddNibbles),
+        0x6000..=0x6FFF => op(LdByte),
+        0x7000..=0x7FFF => op(AddByte),
+        code if code & 0xF00F == 0x9000 => op(SneReg),
+        0xA000..=0xAFFF => op(LdI),
+        0xB000..=0xBFFF if chip8x => op(Colour),
+        0xB000..=0xBFFF => op(JpV0),
+        0xC000..=0xCFFF => op(Rnd),
+        0xD000..=0xDFFF if mega => op(MegaDrw),
+        0xD000..=0xDFFF => op(Drw),
+        code if code & 0xF0FF == 0xE09E => op(Skp),
+        code if code & 0xF0FF == 0xE0A1 => op(Sknp),
+        code if chip8x && code & 0xF0FF == 0xE0F2 => op(Skp2),
+        code if chip8x && code & 0xF0FF == 0xE0F5 => op(Sknp2),
+        code if code & 0xF0FF == 0xF007 => op(LdVxDt),
+        code if code & 0xF0FF == 0xF00A => op(LdVxK),
+        code if code & 0xF0FF == 0xF015 => op(LdDtVx),
+        code if code & 0xF0FF == 0xF018 => op(LdStVx),
+        code if code & 0xF0FF == 0xF01E => op(AddIVx),
+        code if code & 0xF0FF == 0xF029 => op(LdF),
+        code if code & 0xF0FF == 0xF033 => op(LdB),
+        code if code & 0xF0FF == 0xF055 => op(Store),
+        code if code & 0xF0FF == 0xF065 => op(Load),
+        code if chip8x && code & 0xF0FF == 0xF0F8 => op(Pitch),
+        code => match code & 0xF00F {
+            0x8000 => op(LdReg),
+            0x8001 => op(Or),
+            0x8002 => op(And),
+            0x8003 => op(Xor),
+            0x8004 => op(AddReg),
+            0x8005 => op(Sub),
+            0x8006 => op(Shr),
+            0x8007 => op(Subn),
+            0x800e => op(Shl),
+            _ => op(Unknown),
+        },
+    };
+    handler(core, cpu, opcode);
+}
+
+/// Runs TICKS instructions by blocks.
+fn run_blocks(core: &mut CpuCore, cpu: &mut Cpu) {
+    let mut ran = 0;
+    while ran < TICKS as u32 {
+        ran += core.run_block(cpu, TICKS as u32 - ran);
+    }
+}
+
+/// All end in the same state, or the comparison means nothing.
+fn check() {
+    let (mut dispatching, mut decoded, mut translated) = (cpu(), cpu(), cpu());
+    let mut core = CpuCore::new(None);
+
+    for _ in 0..TICKS {
+        tick_dispatching(&mut core, &mut dispatching);
+        core.tick(&mut decoded);
+    }
+    run_blocks(&mut core, &mut translated);
+    for cpu in [&decoded, &translated] {
+        assert_eq!((dispatching.pc, dispatching.i, dispatching.v), (cpu.pc, cpu.i, cpu.v));
+        assert_eq!(dispatching.mem, cpu.mem);
+        assert_eq!(dispatching.vram, cpu.vram);
+    }
+}
+
+fn decoder(c: &mut Criterion) {
+    check();
+    let mut group = c.benchmark_group("tick");
+    group.throughput(Throughput::Elements(TICKS));
+
+    group.bench_function("dispatching every tick", |b| {
+        let (mut core, mut cpu) = (CpuCore::new(None), cpu());
+        b.iter(|| (0..TICKS).for_each(|_| tick_dispatching(black_box(&mut core), &mut cpu)))
+    });
+    group.bench_function("decoded once", |b| {
+        let (mut core, mut cpu) = (CpuCore::new(None), cpu());
+        b.iter(|| (0..TICKS).for_each(|_| black_box(&mut core).tick(&mut cpu)))
+    });
+    group.bench_function("translated blocks", |b| {
+        let (mut core, mut cpu) = (CpuCore::new(None), cpu());
+        b.iter(|| run_blocks(black_box(&mut core), &mut cpu))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decoder);
+criterion_main!(benches);
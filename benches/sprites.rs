@@ -0,0 +1,120 @@
+//! Dxyn on the 64x32 display: the bit-packed FrameBuffer drawing a sprite byte with a shift and
+//! an XOR, against the pixel by pixel loop it replaced and the byte per pixel vram before it.
+//!
+//! `cargo bench --bench sprites`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use oxidate::frame_buffer::FrameBuffer;
+use std::hint::black_box;
+
+const W: usize = 64;
+const H: usize = 32;
+/// A 15 rows high sprite, the largest Dxyn draws.
+const SPRITE: [u8; 15] = [
+    0x3C, 0x42, 0x81, 0xA5, 0x81, 0x99, 0x42, 0x3C, 0x18, 0x7E, 0x18, 0x18, 0x24, 0x42, 0x81,
+];
+
+/// The sprite drawn all over the display, the edges included.
+fn positions() -> impl Iterator<Item = (usize, usize)> {
+    (0..H).step_by(2).flat_map(|y| (0..W).step_by(3).map(move |x| (x, y)))
+}
+
+/// The Dxyn loop over a byte per pixel vram, a branch per bit.
+fn draw_bytes(vram: &mut [u8; W * H], x: usize, y: usize, wrap: bool) -> bool {
+    let mut collision = false;
+    for (j, &byte) in SPRITE.iter().enumerate() {
+        let row = y + j;
+        if row >= H && !wrap {
+            break;
+        }
+        for i in 0..8 {
+            let col = x + i;
+            if col >= W && !wrap {
+                break;
+            }
+            let pixel = &mut vram[(row % H) * W + col % W];
+            let (sprite, set) = ((byte >> (7 - i)) & 1 == 1, *pixel != 0);
+            *pixel = u8::from(sprite ^ set);
+            collision |= sprite & set;
+        }
+    }
+    collision
+}
+
+/// The same loop flipping the pixels of a FrameBuffer one at a time.
+fn draw_pixels(vram: &mut FrameBuffer, x: usize, y: usize, wrap: bool) -> bool {
+    let mut collision = false;
+    for (j, &byte) in SPRITE.iter().enumerate() {
+        let row = y + j;
+        if row >= H && !wrap {
+            break;
+        }
+        for i in 0..8 {
+            let col = x + i;
+            if col >= W && !wrap {
+                break;
+            }
+            if (byte >> (7 - i)) & 1 == 1 {
+                let set = vram.get(col % W, row % H) != 0;
+                vram.set(col % W, row % H, u8::from(!set));
+                collision |= set;
+            }
+        }
+    }
+    collision
+}
+
+/// What op_dxyn does: a sprite byte at a time.
+fn draw_packed(vram: &mut FrameBuffer, x: usize, y: usize, wrap: bool) -> bool {
+    let mut collision = false;
+    for (j, &byte) in SPRITE.iter().enumerate() {
+        let row = y + j;
+        if row >= H && !wrap {
+            break;
+        }
+        collision |= vram.xor_byte(0, x, row % H, byte, wrap);
+    }
+    collision
+}
+
+/// The three draw the same pixels and collisions, or the comparison means nothing.
+fn check(wrap: bool) {
+    let mut bytes = [0; W * H];
+    let mut pixels = FrameBuffer::new(W, H, 1);
+    let mut packed = FrameBuffer::new(W, H, 1);
+
+    for (x, y) in positions() {
+        let collision = draw_bytes(&mut bytes, x, y, wrap);
+        assert_eq!(draw_pixels(&mut pixels, x, y, wrap), collision);
+        assert_eq!(draw_packed(&mut packed, x, y, wrap), collision);
+    }
+    assert_eq!(pixels, packed);
+    assert!(packed.pixels().eq(bytes.iter().copied()));
+}
+
+fn sprites(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dxyn");
+
+    for wrap in [false, true] {
+        check(wrap);
+        let name = |what: &str| format!("{what}, {}", if wrap { "wrapped" } else { "clipped" });
+
+        group.bench_function(name("byte per pixel"), |b| {
+            let mut vram = [0; W * H];
+            b.iter(|| positions().fold(false, |vf, (x, y)| vf | draw_bytes(black_box(&mut vram), x, y, wrap)))
+        });
+        group.bench_function(name("pixel by pixel"), |b| {
+            let mut vram = FrameBuffer::new(W, H, 1);
+            b.iter(|| positions().fold(false, |vf, (x, y)| vf | draw_pixels(black_box(&mut vram), x, y, wrap)))
+        });
+        group.bench_function(name("packed rows"), |b| {
+            let mut vram = FrameBuffer::new(W, H, 1);
+            b.iter(|| positions().fold(false, |vf, (x, y)| vf | draw_packed(black_box(&mut vram), x, y, wrap)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, sprites);
+criterion_main!(benches);
@@ -0,0 +1,367 @@
+//! Static analysis of a ROM: follows the control flow from the start address to find the
+//! reachable code, without running it.
+
+use crate::cpu::Cpu;
+use crate::cpu_core::{CpuCore, START_ADDRESS};
+use crate::instruction::Instruction;
+use crate::quirks::Platform;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where the execution goes after an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    /// The next instruction.
+    Next,
+    /// 1nnn
+    Jump(u16),
+    /// 2nnn, then the next instruction on return.
+    Call(u16),
+    /// 00EE
+    Return,
+    /// 00FD, the SCHIP exit.
+    Exit,
+    /// A conditional skip: the next instruction or the one after it.
+    Skip,
+    /// Bnnn, the target depends on a register.
+    Indirect,
+}
+
+impl Flow {
+    fn of(instruction: Instruction, opcode: u16) -> Self {
+        let nnn = opcode & 0x0FFF;
+
+        match instruction {
+            Instruction::Ret => Flow::Return,
+            Instruction::Exit => Flow::Exit,
+            Instruction::Jp => Flow::Jump(nnn),
+            Instruction::Call => Flow::Call(nnn),
+            Instruction::SeByte
+            | Instruction::SneByte
+            | Instruction::SeReg
+            | Instruction::SneReg
+            | Instruction::Skp
+            | Instruction::Sknp
+            | Instruction::Skp2
+            | Instruction::Sknp2 => Flow::Skip,
+            Instruction::JpV0 => Flow::Indirect,
+            _ => Flow::Next,
+        }
+    }
+}
+
+/// The instruction sets a ROM can use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Family {
+    Chip8,
+    /// 0nnn calls to COSMAC VIP machine code.
+    Machine,
+    Schip,
+    Xochip,
+}
+
+impl Family {
+    /// The instruction set `instruction`, decoded from `opcode`, belongs to.
+    fn of(instruction: Instruction, opcode: u16) -> Self {
+        use Instruction::*;
+
+        match instruction {
+            Sys | MachineRet => Family::Machine,
+            ScrollDown | ScrollRight | ScrollLeft | Exit | Low | High | LdHf | SaveFlags
+            | LoadFlags => Family::Schip,
+            // The 16x16 sprites.
+            Drw if opcode & 0x000F == 0 => Family::Schip,
+            ScrollUp | SaveRange | LoadRange | LdIWord | Plane | Audio | AudioPitch => Family::Xochip,
+            _ => Family::Chip8,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Chip8 => "CHIP-8",
+            Family::Machine => "VIP machine code",
+            Family::Schip => "SCHIP",
+            Family::Xochip => "XO-CHIP",
+        }
+    }
+}
+
+/// An instruction of the ROM, where it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decoded {
+    pub addr: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    /// 2 bytes, 4 for the XO-CHIP F000 nnnn.
+    pub len: u16,
+    pub flow: Flow,
+}
+
+impl Decoded {
+    /// The addresses the execution can continue at, within the program or not.
+    pub fn successors(&self, next_len: impl Fn(u16) -> u16) -> Vec<u16> {
+        let next = self.addr.wrapping_add(self.len);
+        match self.flow {
+            Flow::Next => vec![next],
+            Flow::Jump(target) => vec![target],
+            Flow::Call(target) => vec![target, next],
+            Flow::Return | Flow::Exit | Flow::Indirect => vec![],
+            Flow::Skip => vec![next, next.wrapping_add(next_len(next))],
+        }
+    }
+}
+
+/// A place where the program behaves differently depending on a quirk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuirkUse {
+    pub quirk: &'static str,
+    pub addr: u16,
+    pub note: String,
+}
+
+/// The reachable code of a ROM loaded at the start address.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub instructions: BTreeMap<u16, Decoded>,
+    /// 2nnn targets.
+    pub subroutines: BTreeSet<u16>,
+}
+
+/// Decodes the opcodes with CpuCore::decode, on XO-CHIP whose instructions include the
+/// SUPER-CHIP ones. Nothing runs: the modes a ROM switches to (hi-res, MegaChip) are left out.
+pub struct Decoder {
+    core: CpuCore,
+    cpu: Cpu,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        let mut core = CpuCore::new(None);
+        core.platform = Platform::Xochip;
+        Self { core, cpu: Cpu::new() }
+    }
+
+    pub fn decode(&self, opcode: u16) -> Instruction {
+        self.core.decode(&self.cpu, opcode)
+    }
+
+    /// The instruction at `addr` and how it moves the program counter.
+    pub fn decode_at(&self, addr: u16, opcode: u16) -> Decoded {
+        let instruction = self.decode(opcode);
+        Decoded { addr, opcode, instruction, len: instruction.len(), flow: Flow::of(instruction, opcode) }
+    }
+}
+
+impl Analysis {
+    /// Follows every path from the start address through the ROM.
+    pub fn new(rom: &[u8]) -> Self {
+        let start = START_ADDRESS as u16;
+        let end = start as usize + rom.len();
+        let word = |addr: u16| -> Option<u16> {
+            let at = usize::from(addr);
+            (at >= usize::from(start) && at + 1 < end)
+                .then(|| u16::from_be_bytes([rom[at - start as usize], rom[at + 1 - start as usize]]))
+        };
+        let decoder = Decoder::new();
+        let len_at = |addr: u16| word(addr).map_or(2, |opcode| decoder.decode(opcode).len());
+
+        let mut analysis = Self::default();
+        let mut pending = vec![start];
+
+        while let Some(addr) = pending.pop() {
+            if analysis.instructions.contains_key(&addr) {
+                continue;
+            }
+            let Some(opcode) = word(addr) else { continue };
+
+            let instruction = decoder.decode_at(addr, opcode);
+            if let Flow::Call(target) = instruction.flow {
+                analysis.subroutines.insert(target);
+            }
+            pending.extend(instruction.successors(len_at));
+            analysis.instructions.insert(addr, instruction);
+        }
+
+        analysis
+    }
+
+    /// The addresses of the reachable instructions of every family. Instructions the program
+    /// overwrites are left out, their opcode is only a placeholder.
+    pub fn families(&self) -> BTreeMap<Family, Vec<u16>> {
+        let patched = self.patched();
+        let mut families: BTreeMap<Family, Vec<u16>> = BTreeMap::new();
+        for i in self.instructions.values().filter(|i| !patched.contains(&i.addr)) {
+            families.entry(Family::of(i.instruction, i.opcode)).or_default().push(i.addr);
+        }
+        families
+    }
+
+    /// Code addresses written by an Fx55 after an Annn in the same straight line code:
+    /// self-modifying code.
+    pub fn patched(&self) -> BTreeSet<u16> {
+        let mut patched = BTreeSet::new();
+
+        for i in self.instructions.values().filter(|i| i.instruction == Instruction::LdI) {
+            if let Some(store) = self.next_i_use(i)
+                && store.instruction == Instruction::Store
+            {
+                let start = i.opcode & 0x0FFF;
+                let end = start + ((store.opcode >> 8) & 0xF);
+                patched.extend((start..=end).filter(|a| self.instructions.contains_key(a)));
+            }
+        }
+
+        patched
+    }
+
+    /// Code whose behaviour depends on the shift, load/store and jump quirks.
+    pub fn quirk_uses(&self) -> Vec<QuirkUse> {
+        let mut uses = Vec::new();
+
+        for i in self.instructions.values() {
+            let x = (i.opcode >> 8) & 0xF;
+            let y = (i.opcode >> 4) & 0xF;
+
+            if matches!(i.instruction, Instruction::Shr | Instruction::Shl) && x != y {
+                uses.push(QuirkUse {
+                    quirk: "shift",
+                    addr: i.addr,
+                    note: format!("{:04X} shifts V{x:X} or V{y:X}", i.opcode),
+                });
+            }
+
+            if i.instruction == Instruction::JpV0 && x != 0 {
+                uses.push(QuirkUse {
+                    quirk: "jump",
+                    addr: i.addr,
+                    note: format!("{:04X} adds V0 or V{x:X}", i.opcode),
+                });
+            }
+
+            if matches!(i.instruction, Instruction::Store | Instruction::Load)
+                && let Some(user) = self.next_i_use(i)
+            {
+                uses.push(QuirkUse {
+                    quirk: "load/store",
+                    addr: i.addr,
+                    note: format!(
+                        "{:04X} moves I or not, {:04X} at {:03X} uses it",
+                        i.opcode, user.opcode, user.addr
+                    ),
+                });
+            }
+        }
+
+        uses
+    }
+
+    /// The instruction reading I next in the straight line code after `from`, unless I is set
+    /// before.
+    fn next_i_use(&self, from: &Decoded) -> Option<&Decoded> {
+        use Instruction::*;
+
+        let mut addr = from.addr.wrapping_add(from.len);
+
+        while let Some(i) = self.instructions.get(&addr) {
+            match i.instruction {
+                LdI | LdIWord | LdF | LdHf => return None,
+                Drw | AddIVx | LdB | Store | Load => return Some(i),
+                _ => {}
+            }
+            if i.flow != Flow::Next {
+                return None;
+            }
+            addr = addr.wrapping_add(i.len);
+        }
+
+        None
+    }
+
+    /// The platform the opcodes ask for.
+    pub fn platform(&self) -> Platform {
+        let families = self.families();
+
+        if families.contains_key(&Family::Xochip) {
+            Platform::Xochip
+        } else if families.contains_key(&Family::Schip) {
+            Platform::Schip
+        } else if families.contains_key(&Family::Machine) {
+            Platform::Chip8
+        } else {
+            Platform::Modern
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(opcodes: &[u16]) -> Vec<u8> {
+        opcodes.iter().flat_map(|op| op.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn follows_jumps_calls_and_skips() {
+        let analysis = Analysis::new(&rom(&[
+            0x2208, // 200 call 208
+            0x3000, // 202 skip if V0 = 0
+            0x1206, // 204 jump 206
+            0x1206, // 206 jump 206
+            0x00EE, // 208 return
+            0xFFFF, // 20A unreachable
+        ]));
+
+        assert_eq!(
+            analysis.instructions.keys().copied().collect::<Vec<_>>(),
+            [0x200, 0x202, 0x204, 0x206, 0x208]
+        );
+        assert_eq!(analysis.subroutines, BTreeSet::from([0x208]));
+    }
+
+    #[test]
+    fn skips_jump_over_the_long_xo_chip_load() {
+        let analysis = Analysis::new(&rom(&[0x3000, 0xF000, 0x0300, 0x1206]));
+
+        assert!(analysis.instructions.contains_key(&0x206));
+        assert!(!analysis.instructions.contains_key(&0x204));
+        assert_eq!(analysis.platform(), Platform::Xochip);
+    }
+
+    #[test]
+    fn families_suggest_the_platform() {
+        assert_eq!(Analysis::new(&rom(&[0x00E0, 0x1202])).platform(), Platform::Modern);
+        assert_eq!(Analysis::new(&rom(&[0x0300, 0x1202])).platform(), Platform::Chip8);
+        assert_eq!(Analysis::new(&rom(&[0x00FF, 0x1202])).platform(), Platform::Schip);
+        assert_eq!(Analysis::new(&rom(&[0xF201, 0x1202])).platform(), Platform::Xochip);
+    }
+
+    #[test]
+    fn quirk_sensitive_code_is_flagged() {
+        let analysis = Analysis::new(&rom(&[
+            0x8126, // 200 shift
+            0x8116, // 202 same register, fine
+            0xF255, // 204 store then draw: I moved or not
+            0xD125, // 206
+            0xF265, // 208 load then I set again: fine
+            0xA300, // 20A
+            0xB210, // 20C jump with x != 0
+        ]));
+
+        let quirks: Vec<_> = analysis.quirk_uses().into_iter().map(|q| (q.quirk, q.addr)).collect();
+        assert_eq!(quirks, [("shift", 0x200), ("load/store", 0x204), ("jump", 0x20C)]);
+    }
+
+    #[test]
+    fn patched_placeholders_are_not_counted() {
+        let analysis = Analysis::new(&rom(&[
+            0xA206, // 200 I = 206
+            0xF155, // 202 store V0 V1 over 206
+            0x1206, // 204
+            0x0000, // 206 placeholder
+        ]));
+
+        assert_eq!(analysis.patched(), BTreeSet::from([0x206]));
+        assert!(!analysis.families().contains_key(&Family::Machine));
+        assert_eq!(analysis.platform(), Platform::Modern);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::control::Command;
+use crate::megachip::{Sample, Sound};
+use anyhow::Result;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::sync::mpsc::Sender;
+
+/// Square wave pitch in Hz.
+pub const DEFAULT_PITCH: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.2;
+/// The pitches the config file and the command line accept, in Hz.
+pub const PITCH_RANGE: RangeInclusive<f32> = 20.0..=20_000.0;
+/// The volumes the config file and the command line accept.
+pub const VOLUME_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+/// A MegaChip sample being played.
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+struct Playback {
+    sample: Sample,
+    /// In samples of the sample.
+    position: f32,
+}
+
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+impl Playback {
+    /// The next value, -1.0 - 1.0, for an output playing `rate` values per second. `None`
+    /// once a sample played once is over.
+    fn advance(&mut self, rate: f32) -> Option<f32> {
+        let len = self.sample.data.len() as f32;
+        if self.position >= len {
+            if !self.sample.looped || len == 0.0 {
+                return None;
+            }
+            self.position %= len;
+        }
+
+        let value = f32::from(self.sample.data[self.position as usize]) / 128.0 - 1.0;
+        self.position += self.sample.rate as f32 / rate;
+        Some(value)
+    }
+}
+
+/// The playback shared with the audio callback, which only tries to lock it.
+type SharedPlayback = Arc<Mutex<Option<Playback>>>;
+
+/// The CHIP-8 buzzer, a square wave played on the default output device while the sound
+/// timer runs, or the MegaChip samples. It can also tick the CPU thread every 60th of a
+/// second of played samples.
+pub struct Beeper {
+    tone: Arc<AtomicBool>,
+    /// The pitch played, f32 bits.
+    pitch: Arc<AtomicU32>,
+    playback: SharedPlayback,
+    default_pitch: f32,
+    _stream: device::Stream,
+}
+
+impl Beeper {
+    /// Opens the default output device to play a `pitch` Hz square wave at `volume` (0.0 -
+    /// 1.0). With `ticks` a `Command::Tick(Sync::Audio)` is sent every 60th of a second of audio.
+    pub fn open(pitch: f32, volume: f32, ticks: Option<Sender<Command>>) -> Result<Self> {
+        let tone = Arc::new(AtomicBool::new(false));
+        let bits = Arc::new(AtomicU32::new(pitch.to_bits()));
+        let playback = SharedPlayback::default();
+        Ok(Self {
+            _stream: device::open(Arc::clone(&tone), Arc::clone(&bits), Arc::clone(&playback), volume, ticks)?,
+            tone,
+            pitch: bits,
+            playback,
+            default_pitch: pitch,
+        })
+    }
+
+    pub fn set_tone(&self, on: bool) {
+        self.tone.store(on, Ordering::Relaxed);
+    }
+
+    /// Plays `pitch` Hz instead of the pitch it was opened with, `None` to go back to it.
+    pub fn set_pitch(&self, pitch: Option<f32>) {
+        self.pitch.store(pitch.unwrap_or(self.default_pitch).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Plays a MegaChip sample instead of the buzzer, or stops it.
+    pub fn play(&self, sound: Sound) {
+        let playback = match sound {
+            Sound::Play(sample) => Some(Playback { sample, position: 0.0 }),
+            Sound::Stop => None,
+        };
+        *self.playback.lock().unwrap_or_else(PoisonError::into_inner) = playback;
+    }
+}
+
+#[cfg(feature = "audio")]
+mod device {
+    use super::*;
+    use crate::clock::Sync;
+    use anyhow::anyhow;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{Device, FromSample, SampleFormat, SizedSample, StreamConfig};
+
+    pub type Stream = cpal::Stream;
+
+    pub fn open(
+        tone: Arc<AtomicBool>,
+        pitch: Arc<AtomicU32>,
+        playback: SharedPlayback,
+        volume: f32,
+        ticks: Option<Sender<Command>>,
+    ) -> Result<Stream> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| anyhow!("No audio output device"))?;
+        let supported = device.default_output_config()?;
+        let format = supported.sample_format();
+        let config = supported.config();
+
+        let stream = match format {
+            SampleFormat::F32 => build::<f32>(&device, &config, tone, pitch, playback, volume, ticks)?,
+            SampleFormat::I16 => build::<i16>(&device, &config, tone, pitch, playback, volume, ticks)?,
+            SampleFormat::U16 => build::<u16>(&device, &config, tone, pitch, playback, volume, ticks)?,
+            f => return Err(anyhow!("Unsupported audio sample format {f}")),
+        };
+
+        stream.play()?;
+        Ok(stream)
+    }
+
+    fn build<T>(
+        device: &Device,
+        config: &StreamConfig,
+        tone: Arc<AtomicBool>,
+        pitch: Arc<AtomicU32>,
+        playback: SharedPlayback,
+        volume: f32,
+        ticks: Option<Sender<Command>>,
+    ) -> Result<Stream>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let rate = config.sample_rate.0 as f32;
+        let channels = usize::from(config.channels);
+        let per_tick = (rate / 60.0).round() as u32;
+        let mut phase = 0.0;
+        let mut played = 0;
+
+        let stream = device.build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let on = tone.load(Ordering::Relaxed);
+                let pitch = f32::from_bits(pitch.load(Ordering::Relaxed));
+                // Skipping the samples for a buffer beats waiting on the CPU thread.
+                let mut playback = playback.try_lock();
+
+                for frame in data.chunks_mut(channels) {
+                    let digitised = match &mut playback {
+                        Ok(playback) => playback.as_mut().and_then(|p| p.advance(rate)),
+                        Err(_) => None,
+                    };
+                    let sample = match (digitised, on, phase < 0.5) {
+                        (Some(value), _, _) => value * volume,
+                        (None, false, _) => 0.0,
+                        (None, true, true) => volume,
+                        (None, true, false) => -volume,
+                    };
+                    frame.fill(T::from_sample(sample));
+                    phase = (phase + pitch / rate) % 1.0;
+
+                    played += 1;
+                    if played == per_tick {
+                        played = 0;
+                        if let Some(ticks) = &ticks {
+                            let _ = ticks.send(Command::Tick(Sync::Audio));
+                        }
+                    }
+                }
+            },
+            |e| eprintln!("Audio error: {e}"),
+            None,
+        )?;
+
+        Ok(stream)
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod device {
+    use super::*;
+    use anyhow::bail;
+
+    pub type Stream = ();
+
+    pub fn open(
+        _: Arc<AtomicBool>,
+        _: Arc<AtomicU32>,
+        _: SharedPlayback,
+        _: f32,
+        _: Option<Sender<Command>>,
+    ) -> Result<Stream> {
+        bail!("oxidate was built without the audio feature")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_play_at_their_rate() {
+        let sample = Sample { rate: 4000, data: vec![0x80, 0xFF, 0x00], looped: true };
+        let mut playback = Playback { sample, position: 0.0 };
+
+        // Twice the sample rate: every value twice, then again from the start.
+        let values: Vec<_> = (0..8).map_while(|_| playback.advance(8000.0)).collect();
+        assert_eq!(values, [0.0, 0.0, 0.9921875, 0.9921875, -1.0, -1.0, 0.0, 0.0]);
+
+        playback.sample.looped = false;
+        playback.position = 3.0;
+        assert_eq!(playback.advance(8000.0), None);
+    }
+}
@@ -0,0 +1,792 @@
+//! The RCA CDP1802 COSMAC microprocessor, the CPU of the COSMAC VIP that ran the original
+//! CHIP-8 interpreter. See docs/CDP1802.pdf and docs/1802usermanual.pdf.
+//!
+//! Every instruction takes 2 machine cycles of 8 clock pulses, fetch and execute, but the long
+//! branches, long skips and NOP take 3. DMA and interrupt requests are served between
+//! instructions and take 1 machine cycle each.
+
+/// Clock pulses per machine cycle.
+pub const CLOCKS_PER_CYCLE: u32 = 8;
+
+/// What the CPU is wired to: memory and the I/O devices selected by the N lines.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// INP 1 - 7: the byte a device puts on the data bus.
+    fn input(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    /// OUT 1 - 7: a device takes the byte on the data bus.
+    fn output(&mut self, _port: u8, _value: u8) {}
+}
+
+pub struct Cdp1802 {
+    /// The scratchpad registers R0 - RF.
+    pub r: [u16; 16],
+    /// Designates the program counter among the R registers.
+    pub p: u8,
+    /// Designates the data pointer among the R registers.
+    pub x: u8,
+    /// The accumulator.
+    pub d: u8,
+    /// Carry, or no borrow.
+    pub df: bool,
+    /// X and P saved by an interrupt or MARK.
+    pub t: u8,
+    /// Interrupts enabled.
+    pub ie: bool,
+    /// The Q output line.
+    pub q: bool,
+    /// The EF1 - EF4 input flags, true when asserted.
+    pub ef: [bool; 4],
+    /// The INT input line, true when asserted.
+    pub int: bool,
+    /// Set by IDL, until a DMA or interrupt request.
+    pub idle: bool,
+    /// Machine cycles run since reset.
+    pub cycles: u64,
+}
+
+impl Cdp1802 {
+    pub fn new() -> Self {
+        let mut cpu = Self {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            ef: [false; 4],
+            int: false,
+            idle: false,
+            cycles: 0,
+        };
+        cpu.reset();
+        cpu
+    }
+
+    /// The CLEAR input: the program starts at 0000 with R0 as the program counter.
+    pub fn reset(&mut self) {
+        self.p = 0;
+        self.x = 0;
+        self.r[0] = 0;
+        self.ie = true;
+        self.q = false;
+        self.idle = false;
+    }
+
+    /// Runs one instruction, or serves an interrupt request, or waits one cycle when idle.
+    /// Returns the machine cycles it took.
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        let cycles = if self.int && self.ie {
+            self.interrupt();
+            1
+        } else if self.idle {
+            1
+        } else {
+            let opcode = self.fetch(bus);
+            self.execute(bus, opcode)
+        };
+
+        self.cycles += u64::from(cycles);
+        cycles
+    }
+
+    /// A DMA-OUT cycle: a device reads the byte at R0. Takes 1 machine cycle.
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        self.cycles += 1;
+        value
+    }
+
+    /// A DMA-IN cycle: a device writes a byte at R0. Takes 1 machine cycle.
+    #[allow(dead_code)] // No VIP device writes by DMA.
+    pub fn dma_in(&mut self, bus: &mut impl Bus, value: u8) {
+        bus.write(self.r[0], value);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        self.cycles += 1;
+    }
+
+    /// Saves X and P in T, then runs R1 with R2 as the data pointer, interrupts disabled.
+    fn interrupt(&mut self) {
+        self.t = (self.x << 4) | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.r[usize::from(self.p)]);
+        self.inc(self.p);
+        value
+    }
+
+    fn inc(&mut self, n: u8) {
+        let r = &mut self.r[usize::from(n)];
+        *r = r.wrapping_add(1);
+    }
+
+    fn dec(&mut self, n: u8) {
+        let r = &mut self.r[usize::from(n)];
+        *r = r.wrapping_sub(1);
+    }
+
+    /// The byte R(X) points to.
+    fn rx(&self, bus: &mut impl Bus) -> u8 {
+        bus.read(self.r[usize::from(self.x)])
+    }
+
+    /// Sets D to a + b + carry, DF to the carry out.
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = u16::from(a) + u16::from(b) + u16::from(carry);
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /// Sets D to a - b - borrow, DF when there is no borrow out.
+    fn sub(&mut self, a: u8, b: u8, borrow: bool) {
+        let diff = i16::from(a) - i16::from(b) - i16::from(borrow);
+        self.d = diff as u8;
+        self.df = diff >= 0;
+    }
+
+    /// The condition of the branches and skips 3x and Cx, by the low 3 bits of the opcode.
+    fn condition(&self, n: u8) -> bool {
+        match n & 0x7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            _ => self.ef[usize::from((n & 0x7) - 4)],
+        }
+    }
+
+    /// Runs an instruction, returns its machine cycles.
+    fn execute(&mut self, bus: &mut impl Bus, opcode: u8) -> u32 {
+        let n = opcode & 0x0F;
+        let rn = usize::from(n);
+        let rp = usize::from(self.p);
+        let rx = usize::from(self.x);
+
+        match opcode >> 4 {
+            // IDL
+            0x0 if n == 0 => self.idle = true,
+            // LDN
+            0x0 => self.d = bus.read(self.r[rn]),
+            // INC
+            0x1 => self.inc(n),
+            // DEC
+            0x2 => self.dec(n),
+            // Short branches: BR BQ BZ BDF B1 B2 B3 B4, SKP BNQ BNZ BNF BN1 BN2 BN3 BN4
+            0x3 => {
+                if self.condition(n) == (n < 8) {
+                    let low = bus.read(self.r[rp]);
+                    self.r[rp] = (self.r[rp] & 0xFF00) | u16::from(low);
+                } else {
+                    self.inc(self.p);
+                }
+            }
+            // LDA
+            0x4 => {
+                self.d = bus.read(self.r[rn]);
+                self.inc(n);
+            }
+            // STR
+            0x5 => bus.write(self.r[rn], self.d),
+            0x6 => match n {
+                // IRX
+                0 => self.inc(self.x),
+                // OUT 1 - 7
+                1..=7 => {
+                    let value = self.rx(bus);
+                    bus.output(n, value);
+                    self.inc(self.x);
+                }
+                // INP 1 - 7, 68 is INP 0 on the 1802
+                _ => {
+                    let value = bus.input(n & 0x7);
+                    bus.write(self.r[rx], value);
+                    self.d = value;
+                }
+            },
+            0x7 => match n {
+                // RET, DIS
+                0x0 | 0x1 => {
+                    let xp = self.rx(bus);
+                    self.inc(self.x);
+                    self.x = xp >> 4;
+                    self.p = xp & 0xF;
+                    self.ie = n == 0;
+                }
+                // LDXA
+                0x2 => {
+                    self.d = self.rx(bus);
+                    self.inc(self.x);
+                }
+                // STXD
+                0x3 => {
+                    bus.write(self.r[rx], self.d);
+                    self.dec(self.x);
+                }
+                // ADC
+                0x4 => self.add(self.rx(bus), self.d, self.df),
+                // SDB
+                0x5 => self.sub(self.rx(bus), self.d, !self.df),
+                // SHRC
+                0x6 => {
+                    let carry = self.d & 1 != 0;
+                    self.d = (self.d >> 1) | (u8::from(self.df) << 7);
+                    self.df = carry;
+                }
+                // SMB
+                0x7 => self.sub(self.d, self.rx(bus), !self.df),
+                // SAV
+                0x8 => bus.write(self.r[rx], self.t),
+                // MARK
+                0x9 => {
+                    self.t = (self.x << 4) | self.p;
+                    bus.write(self.r[2], self.t);
+                    self.x = self.p;
+                    self.dec(2);
+                }
+                // REQ, SEQ
+                0xA | 0xB => self.q = n == 0xB,
+                // ADCI
+                0xC => {
+                    let value = self.fetch(bus);
+                    self.add(value, self.d, self.df);
+                }
+                // SDBI
+                0xD => {
+                    let value = self.fetch(bus);
+                    self.sub(value, self.d, !self.df);
+                }
+                // SHLC
+                0xE => {
+                    let carry = self.d & 0x80 != 0;
+                    self.d = (self.d << 1) | u8::from(self.df);
+                    self.df = carry;
+                }
+                // SMBI
+                _ => {
+                    let value = self.fetch(bus);
+                    self.sub(self.d, value, !self.df);
+                }
+            },
+            // GLO
+            0x8 => self.d = self.r[rn] as u8,
+            // GHI
+            0x9 => self.d = (self.r[rn] >> 8) as u8,
+            // PLO
+            0xA => self.r[rn] = (self.r[rn] & 0xFF00) | u16::from(self.d),
+            // PHI
+            0xB => self.r[rn] = (self.r[rn] & 0x00FF) | (u16::from(self.d) << 8),
+            0xC => {
+                match n {
+                    // NOP
+                    0x4 => {}
+                    // LSIE
+                    0xC => self.long_skip(self.ie),
+                    // Long skips: LSNQ LSNZ LSNF, LSQ LSZ LSDF, on Q, D = 0 and DF
+                    0x5..=0x7 => self.long_skip(!self.condition(n & 0x3)),
+                    0xD..=0xF => self.long_skip(self.condition(n & 0x3)),
+                    // Long branches: LBR LBQ LBZ LBDF, LSKP LBNQ LBNZ LBNF
+                    _ => {
+                        if self.condition(n) == (n < 8) {
+                            let high = bus.read(self.r[rp]);
+                            let low = bus.read(self.r[rp].wrapping_add(1));
+                            self.r[rp] = u16::from_be_bytes([high, low]);
+                        } else {
+                            self.long_skip(true);
+                        }
+                    }
+                }
+                return 3;
+            }
+            // SEP
+            0xD => self.p = n,
+            // SEX
+            0xE => self.x = n,
+            _ => match n {
+                // LDX
+                0x0 => self.d = self.rx(bus),
+                // OR, AND, XOR
+                0x1 => self.d |= self.rx(bus),
+                0x2 => self.d &= self.rx(bus),
+                0x3 => self.d ^= self.rx(bus),
+                // ADD
+                0x4 => self.add(self.rx(bus), self.d, false),
+                // SD
+                0x5 => self.sub(self.rx(bus), self.d, false),
+                // SHR
+                0x6 => {
+                    self.df = self.d & 1 != 0;
+                    self.d >>= 1;
+                }
+                // SM
+                0x7 => self.sub(self.d, self.rx(bus), false),
+                // LDI
+                0x8 => self.d = self.fetch(bus),
+                // ORI, ANI, XRI
+                0x9 => self.d |= self.fetch(bus),
+                0xA => self.d &= self.fetch(bus),
+                0xB => self.d ^= self.fetch(bus),
+                // ADI
+                0xC => {
+                    let value = self.fetch(bus);
+                    self.add(value, self.d, false);
+                }
+                // SDI
+                0xD => {
+                    let value = self.fetch(bus);
+                    self.sub(value, self.d, false);
+                }
+                // SHL
+                0xE => {
+                    self.df = self.d & 0x80 != 0;
+                    self.d <<= 1;
+                }
+                // SMI
+                _ => {
+                    let value = self.fetch(bus);
+                    self.sub(self.d, value, false);
+                }
+            },
+        }
+
+        2
+    }
+
+    /// Skips the 2 bytes after the instruction when `skip`.
+    fn long_skip(&mut self, skip: bool) {
+        if skip {
+            let rp = usize::from(self.p);
+            self.r[rp] = self.r[rp].wrapping_add(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 64 KiB of RAM, and the bytes the OUT instructions sent.
+    struct Ram {
+        mem: Vec<u8>,
+        outputs: Vec<(u8, u8)>,
+    }
+
+    impl Bus for Ram {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.mem[usize::from(addr)]
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.mem[usize::from(addr)] = value;
+        }
+
+        fn input(&mut self, port: u8) -> u8 {
+            0x10 + port
+        }
+
+        fn output(&mut self, port: u8, value: u8) {
+            self.outputs.push((port, value));
+        }
+    }
+
+    /// A CPU after reset with `program` at 0000, and the memory.
+    fn machine(program: &[u8]) -> (Cdp1802, Ram) {
+        let mut ram = Ram { mem: vec![0; 0x10000], outputs: Vec::new() };
+        ram.mem[..program.len()].copy_from_slice(program);
+        (Cdp1802::new(), ram)
+    }
+
+    /// Runs `program` from 0000 for `steps` instructions.
+    fn run(program: &[u8], steps: usize, setup: impl FnOnce(&mut Cdp1802, &mut Ram)) -> (Cdp1802, Ram) {
+        let (mut cpu, mut ram) = machine(program);
+        setup(&mut cpu, &mut ram);
+        for _ in 0..steps {
+            cpu.step(&mut ram);
+        }
+        (cpu, ram)
+    }
+
+    #[test]
+    fn reset_starts_at_0000_with_interrupts_enabled() {
+        let cpu = Cdp1802::new();
+
+        assert_eq!((cpu.p, cpu.x, cpu.r[0]), (0, 0, 0));
+        assert!(cpu.ie);
+        assert!(!cpu.q);
+    }
+
+    #[test]
+    fn idl_waits_for_dma_or_interrupt() {
+        let (mut cpu, mut ram) = run(&[0x00, 0xC4], 3, |_, _| {});
+        assert!(cpu.idle);
+        assert_eq!(cpu.r[0], 1);
+
+        ram.mem[0x100] = 0x42;
+        cpu.r[0] = 0x100;
+        assert_eq!(cpu.dma_out(&mut ram), 0x42);
+        assert!(!cpu.idle);
+        assert_eq!(cpu.r[0], 0x101);
+    }
+
+    #[test]
+    fn ldn_loads_d_from_rn() {
+        let (cpu, _) = run(&[0x05], 1, |cpu, ram| {
+            cpu.r[5] = 0x200;
+            ram.mem[0x200] = 0x99;
+        });
+        assert_eq!(cpu.d, 0x99);
+        assert_eq!(cpu.r[5], 0x200);
+    }
+
+    #[test]
+    fn inc_and_dec_wrap_around() {
+        let (cpu, _) = run(&[0x13, 0x24], 2, |cpu, _| {
+            cpu.r[3] = 0xFFFF;
+            cpu.r[4] = 0x0000;
+        });
+        assert_eq!(cpu.r[3], 0x0000);
+        assert_eq!(cpu.r[4], 0xFFFF);
+    }
+
+    #[test]
+    fn br_replaces_the_low_byte_of_the_program_counter() {
+        let (cpu, _) = run(&[0x30, 0x40], 1, |_, _| {});
+        assert_eq!(cpu.r[0], 0x0040);
+    }
+
+    #[test]
+    fn short_branches_test_q_d_df_and_the_flags() {
+        let branch = |opcode: u8, setup: fn(&mut Cdp1802)| {
+            let (mut cpu, mut ram) = machine(&[opcode, 0x40]);
+            setup(&mut cpu);
+            cpu.step(&mut ram);
+            cpu.r[0] == 0x40
+        };
+
+        assert!(branch(0x31, |cpu| cpu.q = true));
+        assert!(!branch(0x31, |_| {}));
+        assert!(branch(0x32, |cpu| cpu.d = 0));
+        assert!(!branch(0x32, |cpu| cpu.d = 1));
+        assert!(branch(0x33, |cpu| cpu.df = true));
+        assert!(branch(0x34, |cpu| cpu.ef[0] = true));
+        assert!(branch(0x37, |cpu| cpu.ef[3] = true));
+        assert!(!branch(0x36, |cpu| cpu.ef[3] = true));
+    }
+
+    #[test]
+    fn inverted_short_branches_and_skp() {
+        let next = |opcode: u8, setup: fn(&mut Cdp1802)| {
+            let (mut cpu, mut ram) = machine(&[opcode, 0x40]);
+            setup(&mut cpu);
+            cpu.step(&mut ram);
+            cpu.r[0]
+        };
+
+        assert_eq!(next(0x38, |_| {}), 2);
+        assert_eq!(next(0x39, |_| {}), 0x40);
+        assert_eq!(next(0x3A, |cpu| cpu.d = 5), 0x40);
+        assert_eq!(next(0x3B, |cpu| cpu.df = true), 2);
+        assert_eq!(next(0x3C, |_| {}), 0x40);
+        assert_eq!(next(0x3F, |cpu| cpu.ef[3] = true), 2);
+    }
+
+    #[test]
+    fn lda_loads_and_advances() {
+        let (cpu, _) = run(&[0x46], 1, |cpu, ram| {
+            cpu.r[6] = 0x300;
+            ram.mem[0x300] = 0x12;
+        });
+        assert_eq!(cpu.d, 0x12);
+        assert_eq!(cpu.r[6], 0x301);
+    }
+
+    #[test]
+    fn str_stores_d() {
+        let (_, ram) = run(&[0x57], 1, |cpu, _| {
+            cpu.r[7] = 0x400;
+            cpu.d = 0x34;
+        });
+        assert_eq!(ram.mem[0x400], 0x34);
+    }
+
+    #[test]
+    fn irx_increments_rx() {
+        let (cpu, _) = run(&[0xE3, 0x60], 2, |cpu, _| cpu.r[3] = 0x10);
+        assert_eq!(cpu.r[3], 0x11);
+    }
+
+    #[test]
+    fn out_sends_rx_and_advances() {
+        let (cpu, ram) = run(&[0xE3, 0x64], 2, |cpu, ram| {
+            cpu.r[3] = 0x500;
+            ram.mem[0x500] = 0x77;
+        });
+        assert_eq!(ram.outputs, [(4, 0x77)]);
+        assert_eq!(cpu.r[3], 0x501);
+    }
+
+    #[test]
+    fn inp_stores_the_bus_in_rx_and_d() {
+        let (cpu, ram) = run(&[0xE3, 0x6A], 2, |cpu, _| cpu.r[3] = 0x500);
+        assert_eq!(cpu.d, 0x12);
+        assert_eq!(ram.mem[0x500], 0x12);
+        assert_eq!(cpu.r[3], 0x500);
+    }
+
+    #[test]
+    fn ret_and_dis_restore_x_and_p() {
+        for (opcode, ie) in [(0x70, true), (0x71, false)] {
+            let (cpu, _) = run(&[0xE2, opcode], 2, |cpu, ram| {
+                cpu.r[2] = 0x600;
+                cpu.ie = !ie;
+                ram.mem[0x600] = 0x35;
+            });
+            assert_eq!((cpu.x, cpu.p), (3, 5));
+            assert_eq!(cpu.r[2], 0x601);
+            assert_eq!(cpu.ie, ie);
+        }
+    }
+
+    #[test]
+    fn ldxa_and_stxd_move_through_rx() {
+        let (cpu, ram) = run(&[0xE2, 0x72, 0x73], 3, |cpu, ram| {
+            cpu.r[2] = 0x600;
+            ram.mem[0x600] = 0x55;
+        });
+        assert_eq!(cpu.d, 0x55);
+        assert_eq!(ram.mem[0x601], 0x55);
+        assert_eq!(cpu.r[2], 0x600);
+    }
+
+    #[test]
+    fn adc_adds_the_carry() {
+        let (cpu, _) = run(&[0x74], 1, |cpu, ram| {
+            ram.mem[0] = 0x74;
+            cpu.r[0] = 0;
+            cpu.x = 1;
+            cpu.r[1] = 0x10;
+            ram.mem[0x10] = 0xF0;
+            cpu.d = 0x0F;
+            cpu.df = true;
+        });
+        assert_eq!(cpu.d, 0x00);
+        assert!(cpu.df);
+    }
+
+    #[test]
+    fn subtractions_set_df_when_there_is_no_borrow() {
+        let sub = |opcode: u8, d: u8, m: u8, df: bool| {
+            let (cpu, _) = run(&[0xE1, opcode], 2, |cpu, ram| {
+                cpu.r[1] = 0x10;
+                ram.mem[0x10] = m;
+                cpu.d = d;
+                cpu.df = df;
+            });
+            (cpu.d, cpu.df)
+        };
+
+        // SD: M - D, SM: D - M
+        assert_eq!(sub(0xF5, 3, 5, false), (2, true));
+        assert_eq!(sub(0xF5, 5, 3, false), (0xFE, false));
+        assert_eq!(sub(0xF7, 5, 3, false), (2, true));
+        assert_eq!(sub(0xF7, 3, 5, false), (0xFE, false));
+        // SDB and SMB subtract the borrow, NOT DF.
+        assert_eq!(sub(0x75, 3, 5, false), (1, true));
+        assert_eq!(sub(0x75, 3, 5, true), (2, true));
+        assert_eq!(sub(0x77, 5, 3, false), (1, true));
+        assert_eq!(sub(0x77, 0, 0, false), (0xFF, false));
+    }
+
+    #[test]
+    fn shifts_move_bits_through_df() {
+        let shift = |opcode: u8, d: u8, df: bool| {
+            let (cpu, _) = run(&[opcode], 1, |cpu, _| {
+                cpu.d = d;
+                cpu.df = df;
+            });
+            (cpu.d, cpu.df)
+        };
+
+        assert_eq!(shift(0xF6, 0b1000_0001, true), (0b0100_0000, true));
+        assert_eq!(shift(0x76, 0b1000_0001, true), (0b1100_0000, true));
+        assert_eq!(shift(0xFE, 0b1000_0001, true), (0b0000_0010, true));
+        assert_eq!(shift(0x7E, 0b0000_0001, true), (0b0000_0011, false));
+    }
+
+    #[test]
+    fn sav_stores_t() {
+        let (_, ram) = run(&[0xE2, 0x78], 2, |cpu, _| {
+            cpu.r[2] = 0x700;
+            cpu.t = 0xAB;
+        });
+        assert_eq!(ram.mem[0x700], 0xAB);
+    }
+
+    #[test]
+    fn mark_saves_x_and_p_on_the_r2_stack() {
+        let (cpu, ram) = run(&[0xE5, 0x79], 2, |cpu, _| cpu.r[2] = 0x700);
+        assert_eq!(cpu.t, 0x50);
+        assert_eq!(ram.mem[0x700], 0x50);
+        assert_eq!(cpu.x, 0);
+        assert_eq!(cpu.r[2], 0x6FF);
+    }
+
+    #[test]
+    fn req_and_seq_drive_q() {
+        let (cpu, _) = run(&[0x7B], 1, |_, _| {});
+        assert!(cpu.q);
+        let (cpu, _) = run(&[0x7B, 0x7A], 2, |_, _| {});
+        assert!(!cpu.q);
+    }
+
+    #[test]
+    fn immediate_arithmetic() {
+        let immediate = |opcode: u8, d: u8, value: u8, df: bool| {
+            let (cpu, _) = run(&[opcode, value], 1, |cpu, _| {
+                cpu.d = d;
+                cpu.df = df;
+            });
+            assert_eq!(cpu.r[0], 2);
+            (cpu.d, cpu.df)
+        };
+
+        assert_eq!(immediate(0x7C, 0xFF, 0x01, true), (0x01, true));
+        assert_eq!(immediate(0x7D, 0x01, 0x05, false), (0x03, true));
+        assert_eq!(immediate(0x7F, 0x05, 0x01, false), (0x03, true));
+        assert_eq!(immediate(0xFC, 0xFF, 0x02, false), (0x01, true));
+        assert_eq!(immediate(0xFD, 0x02, 0x05, false), (0x03, true));
+        assert_eq!(immediate(0xFF, 0x02, 0x05, false), (0xFD, false));
+        assert_eq!(immediate(0xF8, 0x00, 0x42, false).0, 0x42);
+        assert_eq!(immediate(0xF9, 0x0F, 0xF0, false).0, 0xFF);
+        assert_eq!(immediate(0xFA, 0x3C, 0x0F, false).0, 0x0C);
+        assert_eq!(immediate(0xFB, 0xFF, 0x0F, false).0, 0xF0);
+    }
+
+    #[test]
+    fn logic_and_add_through_rx() {
+        let logic = |opcode: u8, d: u8, m: u8| {
+            let (cpu, _) = run(&[0xE1, opcode], 2, |cpu, ram| {
+                cpu.r[1] = 0x10;
+                ram.mem[0x10] = m;
+                cpu.d = d;
+            });
+            (cpu.d, cpu.df)
+        };
+
+        assert_eq!(logic(0xF0, 0, 0x5A).0, 0x5A);
+        assert_eq!(logic(0xF1, 0x0F, 0xF0).0, 0xFF);
+        assert_eq!(logic(0xF2, 0x3C, 0x0F).0, 0x0C);
+        assert_eq!(logic(0xF3, 0xFF, 0x0F).0, 0xF0);
+        assert_eq!(logic(0xF4, 0x80, 0x80), (0x00, true));
+        assert_eq!(logic(0xF4, 0x01, 0x02), (0x03, false));
+    }
+
+    #[test]
+    fn glo_ghi_plo_phi_move_register_halves() {
+        let (cpu, _) = run(&[0x83, 0xA4, 0x93, 0xB4], 4, |cpu, _| cpu.r[3] = 0x1234);
+        assert_eq!(cpu.r[4], 0x1234);
+        assert_eq!(cpu.d, 0x12);
+    }
+
+    #[test]
+    fn long_branches_take_3_cycles() {
+        let (mut cpu, mut ram) = machine(&[0xC0, 0x12, 0x34]);
+        assert_eq!(cpu.step(&mut ram), 3);
+        assert_eq!(cpu.r[0], 0x1234);
+
+        let (mut cpu, mut ram) = machine(&[0xC2, 0x12, 0x34]);
+        cpu.d = 1;
+        assert_eq!(cpu.step(&mut ram), 3);
+        assert_eq!(cpu.r[0], 3);
+
+        let (mut cpu, mut ram) = machine(&[0xCA, 0x12, 0x34]);
+        cpu.d = 1;
+        cpu.step(&mut ram);
+        assert_eq!(cpu.r[0], 0x1234);
+
+        let (mut cpu, mut ram) = machine(&[0xC8, 0x12, 0x34]);
+        cpu.step(&mut ram);
+        assert_eq!(cpu.r[0], 3);
+    }
+
+    #[test]
+    fn long_skips_and_nop() {
+        let next = |opcode: u8, setup: fn(&mut Cdp1802)| {
+            let (mut cpu, mut ram) = machine(&[opcode]);
+            setup(&mut cpu);
+            assert_eq!(cpu.step(&mut ram), 3);
+            cpu.r[0]
+        };
+
+        assert_eq!(next(0xC4, |_| {}), 1);
+        assert_eq!(next(0xC5, |_| {}), 3);
+        assert_eq!(next(0xC5, |cpu| cpu.q = true), 1);
+        assert_eq!(next(0xC6, |cpu| cpu.d = 1), 3);
+        assert_eq!(next(0xC7, |cpu| cpu.df = true), 1);
+        assert_eq!(next(0xCC, |_| {}), 3);
+        assert_eq!(next(0xCC, |cpu| cpu.ie = false), 1);
+        assert_eq!(next(0xCD, |cpu| cpu.q = true), 3);
+        assert_eq!(next(0xCE, |cpu| cpu.d = 0), 3);
+        assert_eq!(next(0xCF, |_| {}), 1);
+    }
+
+    #[test]
+    fn sep_and_sex_select_the_registers() {
+        let (cpu, _) = run(&[0xE7, 0xD3], 2, |cpu, _| cpu.r[3] = 0x800);
+        assert_eq!((cpu.p, cpu.x), (3, 7));
+
+        let (cpu, _) = run(&[0xD3], 2, |cpu, ram| {
+            cpu.r[3] = 0x800;
+            ram.mem[0x800] = 0x7B;
+        });
+        assert!(cpu.q);
+        assert_eq!(cpu.r[3], 0x801);
+    }
+
+    #[test]
+    fn interrupts_save_x_and_p_and_run_r1() {
+        let (mut cpu, mut ram) = machine(&[0xE4, 0xC4]);
+        cpu.step(&mut ram);
+        cpu.int = true;
+
+        assert_eq!(cpu.step(&mut ram), 1);
+        assert_eq!(cpu.t, 0x40);
+        assert_eq!((cpu.p, cpu.x), (1, 2));
+        assert!(!cpu.ie);
+
+        // Disabled interrupts wait.
+        ram.mem[0] = 0xC4;
+        cpu.step(&mut ram);
+        assert_eq!(cpu.r[1], 1);
+    }
+
+    #[test]
+    fn dma_in_writes_at_r0() {
+        let (mut cpu, mut ram) = machine(&[]);
+        cpu.r[0] = 0x900;
+
+        cpu.dma_in(&mut ram, 0x5A);
+        assert_eq!(ram.mem[0x900], 0x5A);
+        assert_eq!(cpu.r[0], 0x901);
+        assert_eq!(cpu.cycles, 1);
+    }
+
+    #[test]
+    fn instructions_take_2_cycles() {
+        let (cpu, _) = run(&[0xF8, 0x01, 0x1A, 0xE1], 3, |_, _| {});
+        assert_eq!(cpu.cycles, 6);
+    }
+}
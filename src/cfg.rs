@@ -0,0 +1,326 @@
+//! `oxidate cfg rom.ch8`: the basic blocks and the call graph of a ROM, in Graphviz DOT or JSON.
+
+use crate::analysis::{Analysis, Decoded, Flow};
+use crate::disasm::mnemonic;
+use crate::rom_file;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz, e.g. `oxidate cfg rom.ch8 | dot -Tsvg > rom.svg`
+    Dot,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    /// The next instruction, a skip not taken or the return from a call.
+    Next,
+    /// 1nnn
+    Jump,
+    /// A skip taken.
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Line {
+    pub addr: u16,
+    pub opcode: u16,
+    pub text: String,
+}
+
+/// Straight line code, entered at the start only and left at the end only.
+#[derive(Debug, Serialize)]
+pub struct Block {
+    pub start: u16,
+    pub lines: Vec<Line>,
+    /// The block starts a subroutine, a 2nnn target.
+    pub subroutine: bool,
+    /// The block ends with a Bnnn jump, whose targets are unknown.
+    pub indirect: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Edge {
+    pub from: u16,
+    pub to: u16,
+    pub kind: EdgeKind,
+    /// The edge goes back to a block being run: it closes a loop.
+    #[serde(rename = "loop")]
+    pub back: bool,
+}
+
+/// A 2nnn call from the block at `from` to the subroutine at `to`.
+#[derive(Debug, Serialize)]
+pub struct Call {
+    pub from: u16,
+    pub to: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub entry: u16,
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+    pub calls: Vec<Call>,
+    /// The subroutines every routine calls, the entry point being the main routine.
+    pub call_graph: BTreeMap<u16, BTreeSet<u16>>,
+}
+
+pub fn run(rom: &Path, format: GraphFormat, output: Option<&Path>) -> Result<()> {
+    let file = rom_file::read(rom).with_context(|| format!("Cannot read {}", rom.display()))?;
+    let graph = Graph::new(&Analysis::new(&file.data));
+
+    let text = match format {
+        GraphFormat::Dot => graph.dot(),
+        GraphFormat::Json => serde_json::to_string_pretty(&graph)? + "\n",
+    };
+
+    match output {
+        Some(path) => fs::write(path, text).with_context(|| format!("Cannot write {}", path.display())),
+        None => {
+            print!("{text}");
+            Ok(())
+        }
+    }
+}
+
+impl Graph {
+    pub fn new(analysis: &Analysis) -> Self {
+        let code = &analysis.instructions;
+        let len_at = |addr: u16| code.get(&addr).map_or(2, |i| i.len);
+        let entry = code.keys().next().copied().unwrap_or_default();
+
+        // Blocks start at the entry point, at the targets of jumps, calls and skips, and after
+        // every instruction that does not go on to the next one.
+        let mut leaders = BTreeSet::from([entry]);
+        leaders.extend(&analysis.subroutines);
+        for i in code.values().filter(|i| i.flow != Flow::Next) {
+            leaders.extend(i.successors(len_at));
+            leaders.insert(i.addr.wrapping_add(i.len));
+        }
+
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut ends: Vec<&Decoded> = Vec::new();
+        let mut next = None;
+        for i in code.values() {
+            if leaders.contains(&i.addr) || next != Some(i.addr) {
+                blocks.push(Block {
+                    start: i.addr,
+                    lines: Vec::new(),
+                    subroutine: analysis.subroutines.contains(&i.addr),
+                    indirect: false,
+                });
+                ends.push(i);
+            }
+            let block = blocks.last_mut().expect("Bug - every instruction is in a block");
+            block.lines.push(Line { addr: i.addr, opcode: i.opcode, text: mnemonic(i.instruction, i.opcode) });
+            block.indirect = i.flow == Flow::Indirect;
+            *ends.last_mut().expect("Bug - every block has an end") = i;
+            next = Some(i.addr.wrapping_add(i.len));
+        }
+
+        let mut edges = Vec::new();
+        let mut calls = Vec::new();
+        for (block, last) in blocks.iter().zip(&ends) {
+            let after = last.addr.wrapping_add(last.len);
+            let mut edge = |to: u16, kind| {
+                if code.contains_key(&to) {
+                    edges.push(Edge { from: block.start, to, kind, back: false });
+                }
+            };
+
+            match last.flow {
+                Flow::Next => edge(after, EdgeKind::Next),
+                Flow::Jump(target) => edge(target, EdgeKind::Jump),
+                Flow::Call(target) => {
+                    edge(after, EdgeKind::Next);
+                    if code.contains_key(&target) {
+                        calls.push(Call { from: block.start, to: target });
+                    }
+                }
+                Flow::Skip => {
+                    edge(after, EdgeKind::Next);
+                    edge(after.wrapping_add(len_at(after)), EdgeKind::Skip);
+                }
+                Flow::Return | Flow::Exit | Flow::Indirect => {}
+            }
+        }
+
+        let mut graph = Self { entry, blocks, edges, calls, call_graph: BTreeMap::new() };
+        graph.mark_loops();
+        graph.build_call_graph(&analysis.subroutines);
+        graph
+    }
+
+    /// The edges leaving a block.
+    fn successors(&self, from: u16) -> impl Iterator<Item = (usize, &Edge)> {
+        self.edges.iter().enumerate().filter(move |(_, e)| e.from == from)
+    }
+
+    /// Marks the edges going back to a block on the depth first search path from a routine
+    /// entry: the loops.
+    fn mark_loops(&mut self) {
+        let mut roots = vec![self.entry];
+        roots.extend(self.calls.iter().map(|c| c.to));
+
+        let mut visited = BTreeSet::new();
+        let mut back = Vec::new();
+        for root in roots {
+            if visited.contains(&root) {
+                continue;
+            }
+            let mut path = BTreeSet::new();
+            self.search(root, &mut visited, &mut path, &mut back);
+        }
+
+        for index in back {
+            self.edges[index].back = true;
+        }
+    }
+
+    fn search(&self, block: u16, visited: &mut BTreeSet<u16>, path: &mut BTreeSet<u16>, back: &mut Vec<usize>) {
+        visited.insert(block);
+        path.insert(block);
+
+        for (index, edge) in self.successors(block) {
+            if path.contains(&edge.to) {
+                back.push(index);
+            } else if !visited.contains(&edge.to) {
+                self.search(edge.to, visited, path, back);
+            }
+        }
+
+        path.remove(&block);
+    }
+
+    /// The subroutines called from the blocks every routine reaches without calls.
+    fn build_call_graph(&mut self, subroutines: &BTreeSet<u16>) {
+        let routines = std::iter::once(self.entry).chain(subroutines.iter().copied());
+
+        for routine in routines.collect::<Vec<_>>() {
+            let mut reached = BTreeSet::from([routine]);
+            let mut pending = vec![routine];
+            while let Some(block) = pending.pop() {
+                for (_, edge) in self.successors(block) {
+                    if reached.insert(edge.to) {
+                        pending.push(edge.to);
+                    }
+                }
+            }
+
+            let callees = self.calls.iter().filter(|c| reached.contains(&c.from)).map(|c| c.to).collect();
+            self.call_graph.insert(routine, callees);
+        }
+    }
+
+    /// Blocks are boxes listing their instructions. Subroutines have a double border, blocks
+    /// ending with Bnnn are red, skips are dashed, loops are blue and calls are dotted.
+    pub fn dot(&self) -> String {
+        let mut dot = String::from("digraph rom {\n");
+        dot += "  node [shape=box, fontname=\"monospace\"];\n";
+
+        for block in &self.blocks {
+            let mut label = String::new();
+            for line in &block.lines {
+                let _ = write!(label, "{:03X}  {:04X}  {}\\l", line.addr, line.opcode, line.text);
+            }
+            if block.indirect {
+                label += "-> ?\\l";
+            }
+
+            let mut attributes = vec![format!("label=\"{label}\"")];
+            if block.start == self.entry {
+                attributes.push("style=bold".into());
+            }
+            if block.subroutine {
+                attributes.push("peripheries=2".into());
+            }
+            if block.indirect {
+                attributes.push("color=red".into());
+            }
+            let _ = writeln!(dot, "  b{:03X} [{}];", block.start, attributes.join(", "));
+        }
+
+        for edge in &self.edges {
+            let mut attributes = Vec::new();
+            if edge.kind == EdgeKind::Skip {
+                attributes.push("style=dashed, label=\"skip\"");
+            }
+            if edge.back {
+                attributes.push("color=blue");
+            }
+            let _ = write!(dot, "  b{:03X} -> b{:03X}", edge.from, edge.to);
+            if !attributes.is_empty() {
+                let _ = write!(dot, " [{}]", attributes.join(", "));
+            }
+            dot += ";\n";
+        }
+
+        for call in &self.calls {
+            let _ = writeln!(dot, "  b{:03X} -> b{:03X} [style=dotted, label=\"call\"];", call.from, call.to);
+        }
+
+        dot += "}\n";
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(opcodes: &[u16]) -> Graph {
+        let rom: Vec<u8> = opcodes.iter().flat_map(|op| op.to_be_bytes()).collect();
+        Graph::new(&Analysis::new(&rom))
+    }
+
+    #[test]
+    fn blocks_split_at_jumps_calls_and_skips() {
+        let graph = graph(&[
+            0x6000, // 200 LD V0, 0
+            0x220A, // 202 CALL 20A
+            0x3001, // 204 SE V0, 1
+            0x1202, // 206 JP 202
+            0x1208, // 208 JP 208
+            0x7001, // 20A ADD V0, 1
+            0x00EE, // 20C RET
+        ]);
+
+        let starts: Vec<u16> = graph.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(starts, [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]);
+        assert!(graph.blocks[5].subroutine);
+
+        let edges: Vec<_> = graph.edges.iter().map(|e| (e.from, e.to, e.kind, e.back)).collect();
+        assert_eq!(
+            edges,
+            [
+                (0x200, 0x202, EdgeKind::Next, false),
+                (0x202, 0x204, EdgeKind::Next, false),
+                (0x204, 0x206, EdgeKind::Next, false),
+                (0x204, 0x208, EdgeKind::Skip, false),
+                (0x206, 0x202, EdgeKind::Jump, true),
+                (0x208, 0x208, EdgeKind::Jump, true),
+            ]
+        );
+        assert_eq!(graph.call_graph[&0x200], BTreeSet::from([0x20A]));
+        assert_eq!(graph.call_graph[&0x20A], BTreeSet::new());
+    }
+
+    #[test]
+    fn indirect_jumps_end_blocks_without_edges() {
+        let graph = graph(&[0x6000, 0xB300]);
+
+        assert_eq!(graph.blocks.len(), 1);
+        assert!(graph.blocks[0].indirect);
+        assert!(graph.edges.is_empty());
+        assert!(graph.dot().contains("color=red"));
+    }
+}
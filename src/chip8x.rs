@@ -0,0 +1,124 @@
+//! CHIP-8X, the CHIP-8 of the VP-590 colour board and the VP-595 sound board for the VIP.
+//!
+//! The VP-590 colours the pixels set in zones 8 pixels wide, the ones not set show the
+//! background colour. The programs start at 0300, after the larger interpreter.
+
+use crate::config::{H, W};
+use crate::palette::Rgba;
+
+pub const START_ADDRESS: usize = 0x300;
+
+const COLUMNS: usize = W / 8;
+/// BxY0 colours zones of 8x4 pixels.
+const ZONE_H: usize = 4;
+
+/// The VP-590 colours, indexed by their red (1), blue (2) and green (4) bits.
+const COLOURS: [Rgba; 8] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0x00, 0xFF],
+    [0x00, 0x00, 0xFF, 0xFF],
+    [0xFF, 0x00, 0xFF, 0xFF],
+    [0x00, 0xFF, 0x00, 0xFF],
+    [0xFF, 0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+];
+/// The backgrounds 02A0 cycles through: blue, black, green and red.
+const BACKGROUNDS: [u8; 4] = [2, 0, 4, 1];
+const FOREGROUND: u8 = 1;
+
+/// The colours of the VP-590.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColourBoard {
+    /// Index in BACKGROUNDS.
+    background: usize,
+    /// The colour of every 8 pixel wide zone of every row.
+    zones: [u8; COLUMNS * H],
+}
+
+impl Default for ColourBoard {
+    fn default() -> Self {
+        Self { background: 0, zones: [FOREGROUND; COLUMNS * H] }
+    }
+}
+
+impl ColourBoard {
+    /// 02A0
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    /// BxY0: the low nibbles of `x` and `y` are the column and the row of the first zone of
+    /// 8x4 pixels, the high nibbles the number of zones coloured after it.
+    pub fn set_zones(&mut self, x: u8, y: u8, colour: u8) {
+        let (left, width) = (usize::from(x & 0xF), usize::from(x >> 4) + 1);
+        let (top, height) = (usize::from(y & 0xF), usize::from(y >> 4) + 1);
+
+        for row in top * ZONE_H..(top + height) * ZONE_H {
+            for column in left..left + width {
+                self.zones[(row % H) * COLUMNS + column % COLUMNS] = colour & 7;
+            }
+        }
+    }
+
+    /// BxyN: colours `rows` rows of the zone of the pixel at `x`, `y`.
+    pub fn set_rows(&mut self, x: u8, y: u8, rows: u8, colour: u8) {
+        let column = usize::from(x) / 8 % COLUMNS;
+
+        for row in usize::from(y)..usize::from(y) + usize::from(rows) {
+            self.zones[(row % H) * COLUMNS + column] = colour & 7;
+        }
+    }
+
+    /// The colour of the pixel at `x`, `y`, set or not.
+    pub fn color(&self, x: usize, y: usize, on: bool) -> &Rgba {
+        let colour = if on { self.zones[y * COLUMNS + x / 8] } else { BACKGROUNDS[self.background] };
+        &COLOURS[usize::from(colour)]
+    }
+}
+
+/// The pitch of the VP-595 for the value FxF8 outputs.
+pub fn frequency(tone: u8) -> f32 {
+    27_535.0 / (f32::from(tone) + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zones_take_the_colour_of_the_area() {
+        let mut board = ColourBoard::default();
+
+        // The 2x1 zones from column 3 and row 1: x 24 to 39 and y 4 to 7.
+        board.set_zones(0x13, 0x01, 4);
+
+        assert_eq!(board.color(24, 4, true), &COLOURS[4]);
+        assert_eq!(board.color(39, 7, true), &COLOURS[4]);
+        assert_eq!(board.color(40, 4, true), &COLOURS[1]);
+        assert_eq!(board.color(24, 8, true), &COLOURS[1]);
+        assert_eq!(board.color(24, 4, false), &COLOURS[2]);
+    }
+
+    #[test]
+    fn rows_take_the_colour_of_their_zone() {
+        let mut board = ColourBoard::default();
+
+        board.set_rows(10, 30, 3, 7);
+
+        assert_eq!(board.color(8, 31, true), &COLOURS[7]);
+        assert_eq!(board.color(15, 0, true), &COLOURS[7]);
+        assert_eq!(board.color(16, 31, true), &COLOURS[1]);
+        assert_eq!(board.color(8, 29, true), &COLOURS[1]);
+    }
+
+    #[test]
+    fn the_background_cycles() {
+        let mut board = ColourBoard::default();
+
+        for background in [0, 4, 1, 2] {
+            board.cycle_background();
+            assert_eq!(board.color(0, 0, false), &COLOURS[background]);
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! The command line: the emulator with its window or terminal frontend, and the tools.
+
+use crate::audio::{Beeper, DEFAULT_PITCH, DEFAULT_VOLUME, PITCH_RANGE, VOLUME_RANGE};
+use crate::cfg::{self, GraphFormat};
+use crate::clock::Sync;
+use crate::control::{Command, Status};
+use crate::cpu_debugger::CpuDebugger;
+use crate::emulator::{Emulator, DEFAULT_SPEED};
+use crate::inspect;
+use crate::keypad::Keymap;
+use crate::palette::Palette;
+use crate::profiler::{Profiler, DEFAULT_TOP};
+use crate::quirks::Platform;
+use crate::render_filter::{FilterMode, RenderFilter};
+use crate::rom_browser::RomBrowser;
+use crate::rom_db::RomDb;
+use crate::settings::Settings;
+use crate::terminal_video::TerminalVideo;
+use crate::video_input::VideoInput;
+use crate::vip::{Firmware, Vip};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use winit::event_loop::EventLoop;
+
+const DEFAULT_SCALE: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Frontend {
+    /// Graphical window
+    Window,
+    /// Terminal, drawn with half-block characters
+    Tui,
+}
+
+/// Tools working on a ROM without running it.
+#[derive(Subcommand, Debug, Clone)]
+enum Tool {
+    /// Tell the opcodes a ROM uses, the quirks its code depends on and the platform to run it on
+    Inspect { rom: PathBuf },
+    /// Export the basic blocks and the call graph of a ROM
+    Cfg {
+        rom: PathBuf,
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+        /// Write the graph to a file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(name = "oxide", version, about = "A Chip8 Emulator written in Rust")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    tool: Option<Tool>,
+
+    rom_file: Option<String>,
+
+    /// Config file [default: ~/.config/oxidate/config.toml when it exists]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Reload the ROM automatically when the file changes on disk
+    #[arg(long)]
+    watch: bool,
+
+    /// Show the ROM on the 64x64 display of the hi-res VIP interpreter [default: for the ROMs
+    /// starting with its 1260 jump]
+    #[arg(long)]
+    hires: bool,
+
+    /// Emulation speed in instructions per 60 Hz frame [default: from the ROM database, or 10]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    speed: Option<u32>,
+
+    /// Interpreter whose quirks are emulated [default: from the ROM database, otherwise the
+    /// quirks of the previous versions]
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+
+    /// What the frames are paced against
+    #[arg(long, value_enum, default_value_t = Sync::Timer)]
+    sync: Sync,
+
+    /// Show the CPU registers and the measured speed in the terminal (window frontend only)
+    #[arg(long)]
+    debug: bool,
+
+    /// Count where the instructions go and write a report and an annotated disassembly of the
+    /// ROM to this directory on exit
+    #[arg(long, value_name = "DIR")]
+    profile: Option<PathBuf>,
+
+    /// Hot addresses listed in the profile report
+    #[arg(long, default_value_t = DEFAULT_TOP)]
+    profile_top: usize,
+
+    /// Directory listed by the ROM browser (F1) [default: .]
+    #[arg(long)]
+    rom_dir: Option<PathBuf>,
+
+    /// Where the game screen is shown
+    #[arg(long, value_enum, default_value_t = Frontend::Window)]
+    frontend: Frontend,
+
+    /// Display palette: brown, amber, green, lcd or contrast [default: brown]
+    #[arg(long, value_parser = parse_palette)]
+    palette: Option<String>,
+
+    /// Custom palette as 2 or 4 comma separated hex colours (e.g. "#000000,#33ff66")
+    #[arg(long, value_delimiter = ',')]
+    colors: Vec<String>,
+
+    /// Flicker reduction filter, F3 cycles through them at runtime
+    #[arg(long, value_enum, default_value_t = FilterMode::Off)]
+    filter: FilterMode,
+
+    /// Brightness an erased pixel keeps on every frame with the fade filter (0.0 - 1.0)
+    #[arg(long, default_value_t = 0.6)]
+    decay: f32,
+
+    /// Initial window size as a multiple of the emulated resolution [default: 10]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=64))]
+    scale: Option<u32>,
+
+    /// The 16 keyboard keys of the CHIP-8 keys 0 to F [default: x123qweasdzc4rfv]
+    #[arg(long)]
+    keys: Option<String>,
+
+    /// The 16 keys of the second CHIP-8X keypad, keys 0 to F [default: m678yuihjkn,9ol.]
+    #[arg(long)]
+    keys2: Option<String>,
+
+    /// Buzzer pitch in Hz, 20 - 20000 [default: 440]
+    #[arg(long, value_parser = parse_pitch)]
+    pitch: Option<f32>,
+
+    /// Buzzer volume (0.0 - 1.0) [default: 0.2]
+    #[arg(long, value_parser = parse_volume)]
+    volume: Option<f32>,
+
+    /// Emulate a COSMAC VIP running the original CHIP-8 interpreter, timing included
+    #[arg(long)]
+    vip: bool,
+
+    /// Dump of the 512 byte VIP monitor ROM, for --vip
+    #[arg(long, value_name = "FILE")]
+    vip_monitor: Option<PathBuf>,
+
+    /// Dump of the VIP CHIP-8 interpreter, the first 512 bytes of its RAM, for --vip
+    #[arg(long, value_name = "FILE")]
+    vip_interpreter: Option<PathBuf>,
+}
+
+impl Args {
+    /// Fills the options missing from the command line with the config file ones.
+    fn merge(&mut self, settings: Settings) {
+        self.speed = self.speed.or(settings.speed);
+        self.platform = self.platform.or(settings.platform);
+        self.palette = self.palette.take().or(settings.palette);
+        if self.colors.is_empty() {
+            self.colors = settings.colors.unwrap_or_default();
+        }
+        self.scale = self.scale.or(settings.scale);
+        self.keys = self.keys.take().or(settings.keys);
+        self.keys2 = self.keys2.take().or(settings.keys2);
+        self.rom_dir = self.rom_dir.take().or(settings.rom_dir);
+        self.pitch = self.pitch.or(settings.audio.pitch);
+        self.volume = self.volume.or(settings.audio.volume);
+        self.vip_monitor = self.vip_monitor.take().or(settings.vip.monitor);
+        self.vip_interpreter = self.vip_interpreter.take().or(settings.vip.interpreter);
+    }
+}
+
+/// Parses the command line and runs what it asks for.
+pub fn run() {
+    let mut args = Args::parse();
+    if let Some(tool) = &args.tool {
+        if let Err(e) = run_tool(tool) {
+            eprintln!("{e:#}");
+            exit(1);
+        }
+        return;
+    }
+    match Settings::load(args.config.as_deref()) {
+        Ok(settings) => args.merge(settings),
+        Err(e) => {
+            eprintln!("{e:#}");
+            exit(1);
+        }
+    }
+    if args.debug && args.frontend == Frontend::Tui {
+        eprintln!("--debug needs the terminal, it cannot be used with --frontend tui");
+        exit(1);
+    }
+
+    let firmware = args.vip.then(|| vip_firmware(&args));
+
+    let (status_tx, status_rx) = mpsc::channel::<Status>();
+    let (cmd_tx, cmd_rx) = mpsc::channel::<Command>();
+    let palettes = palettes(&args);
+    let filter = RenderFilter::new(args.filter, args.decay);
+    let speed = args.speed.unwrap_or(DEFAULT_SPEED);
+    let scale = args.scale.unwrap_or(DEFAULT_SCALE);
+    let keymap = match &args.keys {
+        None => Keymap::default(),
+        Some(keys) => Keymap::from_layout(keys).unwrap_or_else(|| {
+            eprintln!("Invalid --keys: 16 keys are needed, the ones of the CHIP-8 keys 0 to F");
+            exit(1);
+        }),
+    };
+    let keymap2 = match &args.keys2 {
+        None => Keymap::second(),
+        Some(keys) => Keymap::from_layout(keys).unwrap_or_else(|| {
+            eprintln!("Invalid --keys2: 16 keys are needed, the ones of the CHIP-8X keys 0 to F");
+            exit(1);
+        }),
+    };
+
+    let cpu_args = args.clone();
+    let ticks = cmd_tx.clone();
+    let cpu_thread = thread::spawn(move || {
+        run_cpu_thread(cpu_args, firmware, status_tx, cmd_rx, ticks);
+    });
+
+    match args.frontend {
+        Frontend::Window => {
+            let browser = RomBrowser::new(args.rom_dir.clone().unwrap_or_else(|| PathBuf::from(".")));
+            let mut app = VideoInput::new(
+                status_rx, cmd_tx, keymap, keymap2, palettes, filter, scale, speed,
+                browser,
+            );
+            if args.rom_file.is_none() {
+                app.open_browser();
+            }
+            let event_loop = EventLoop::new().unwrap();
+
+            event_loop.run_app(&mut app)
+                .expect("TODO: panic message");
+        }
+        Frontend::Tui => {
+            let mut tui = TerminalVideo::new(status_rx, cmd_tx, keymap, palettes, speed)
+                .expect("Cannot set up the terminal");
+            let result = tui.run();
+
+            tui.quit().unwrap();
+            result.unwrap();
+        }
+    }
+
+    // The frontends send Quit when they close, or drop the command sender when they fail.
+    cpu_thread.join().expect("The CPU thread panicked");
+}
+
+/// Accepts only the names of the built-in palettes.
+fn parse_palette(name: &str) -> Result<String, String> {
+    match Palette::preset(name) {
+        Some(_) => Ok(name.to_string()),
+        None => Err(format!("{name:?} is unknown, use brown, amber, green, lcd or contrast")),
+    }
+}
+
+/// Accepts only the buzzer pitches the config file accepts.
+fn parse_pitch(arg: &str) -> Result<f32, String> {
+    let pitch: f32 = arg.parse().map_err(|e| format!("{e}"))?;
+    if !PITCH_RANGE.contains(&pitch) {
+        return Err(format!("must be between 20 and 20000 Hz, not {pitch}"));
+    }
+    Ok(pitch)
+}
+
+/// Accepts only the buzzer volumes the config file accepts.
+fn parse_volume(arg: &str) -> Result<f32, String> {
+    let volume: f32 = arg.parse().map_err(|e| format!("{e}"))?;
+    if !VOLUME_RANGE.contains(&volume) {
+        return Err(format!("must be between 0.0 and 1.0, not {volume}"));
+    }
+    Ok(volume)
+}
+
+fn run_tool(tool: &Tool) -> anyhow::Result<()> {
+    match tool {
+        Tool::Inspect { rom } => inspect::run(rom),
+        Tool::Cfg { rom, format, output } => cfg::run(rom, *format, output.as_deref()),
+    }
+}
+
+/// The monitor ROM and the interpreter of `--vip`, exits when they are missing.
+fn vip_firmware(args: &Args) -> Firmware {
+    let (Some(monitor), Some(interpreter)) = (&args.vip_monitor, &args.vip_interpreter) else {
+        eprintln!("--vip needs the dumps of the VIP monitor ROM and CHIP-8 interpreter, given with --vip-monitor and --vip-interpreter");
+        exit(1);
+    };
+    Firmware::load(monitor, interpreter).unwrap_or_else(|e| {
+        eprintln!("{e:#}");
+        exit(1);
+    })
+}
+
+/// The palettes the window cycles through, the selected one first.
+fn palettes(args: &Args) -> Vec<Palette> {
+    let selected = if args.colors.is_empty() {
+        let name = args.palette.as_deref().unwrap_or("brown");
+        Palette::preset(name).expect("the palette names are checked when parsed")
+    } else {
+        Palette::custom(&args.colors).unwrap_or_else(|e| {
+            eprintln!("Invalid --colors: {e:#}");
+            exit(1);
+        })
+    };
+
+    let mut palettes = Palette::presets();
+    palettes.retain(|p| *p != selected);
+    palettes.insert(0, selected);
+    palettes
+}
+
+fn run_cpu_thread(
+    args: Args,
+    firmware: Option<Firmware>,
+    status: Sender<Status>,
+    commands: Receiver<Command>,
+    ticks: Sender<Command>,
+) {
+    let mut emulator = Emulator::new(status);
+    emulator.set_watch(args.watch);
+    emulator.set_hires(args.hires);
+    emulator.set_rom_db(RomDb::load());
+    emulator.set_overrides(args.speed, args.platform);
+    emulator.set_sync(args.sync);
+
+    // The audio stream cannot leave the thread that opened it.
+    let ticks = (args.sync == Sync::Audio).then_some(ticks);
+    let pitch = args.pitch.unwrap_or(DEFAULT_PITCH);
+    let volume = args.volume.unwrap_or(DEFAULT_VOLUME);
+    match Beeper::open(pitch, volume, ticks) {
+        Ok(beeper) => emulator.set_beeper(beeper),
+        Err(e) if args.sync == Sync::Audio => {
+            eprintln!("{e:#}, pacing on the timer");
+            emulator.set_sync(Sync::Timer);
+        }
+        Err(e) if cfg!(feature = "audio") => eprintln!("No sound: {e:#}"),
+        Err(_) => {}
+    }
+
+    if args.profile.is_some() {
+        emulator.set_profiler(Profiler::new());
+    }
+    if let Some(firmware) = &firmware {
+        emulator.set_vip(Vip::new(firmware));
+    }
+    if args.debug {
+        emulator.set_debugger(CpuDebugger::new());
+    }
+
+    match args.rom_file {
+        None => emulator.reset(),
+        Some(r) => emulator.load_rom(Path::new(&r)),
+    }
+
+    emulator.run(commands);
+
+    let profiler = emulator.take_profiler();
+    // Leaves the debugger screen before printing.
+    drop(emulator);
+    if let (Some(dir), Some(profiler)) = (&args.profile, profiler) {
+        match profiler.write(dir, args.profile_top) {
+            Ok(()) => println!("Profile written to {}", dir.display()),
+            Err(e) => eprintln!("{e:#}"),
+        }
+    }
+}
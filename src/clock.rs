@@ -1,33 +1,224 @@
-use std::thread::sleep;
+use clap::ValueEnum;
+use std::thread::{sleep, yield_now};
 use std::time::{Duration, Instant};
 
-pub const INTERVAL: Duration = Duration::from_micros(1_666);
+/// One 60 Hz frame.
+pub const INTERVAL: Duration = Duration::from_micros(16_667);
 
+/// After a stall (a slow frame, the machine going to sleep) at most this many frames are run
+/// back to back to catch up, the rest are dropped.
+const MAX_CATCH_UP: u32 = 4;
+/// The OS wakes a sleeping thread late by up to a millisecond or so: sleep until this much
+/// before the deadline, then spin.
+const SPIN: Duration = Duration::from_millis(1);
+/// How often the measured rates are refreshed.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// What the emulator frames are paced against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Sync {
+    /// The system timer.
+    Timer,
+    /// The frames presented by the frontend.
+    Vsync,
+    /// The samples played by the audio device.
+    Audio,
+}
+
+/// Where the clock reads the time and waits: the system one, or a fake one in the tests.
+trait Time {
+    fn now(&self) -> Instant;
+    fn sleep(&mut self, duration: Duration);
+    /// Lets the other threads run while spinning.
+    fn spin(&mut self);
+}
+
+struct Os;
+
+impl Time for Os {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        sleep(duration);
+    }
+
+    fn spin(&mut self) {
+        yield_now();
+    }
+}
+
+/// Frame pacing against absolute deadlines, so the sleep overshoots never add up.
 pub struct Clock {
-    start_time: Instant,
-    end_time: Instant,
+    interval: Duration,
+    deadline: Instant,
+    window_start: Instant,
+    frames: u32,
+    instructions: u64,
+    fps: f64,
+    ips: f64,
 }
 
 impl Clock {
     pub fn new() -> Self {
         Self {
-            start_time: Instant::now(),
-            end_time: Instant::now(),
+            interval: INTERVAL,
+            deadline: Instant::now() + INTERVAL,
+            window_start: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            fps: 0.0,
+            ips: 0.0,
         }
     }
 
-    pub fn start(&mut self) {
-        self.start_time = Instant::now()
+    /// Starts counting frames from now, after a pause or a turbo burst.
+    pub fn restart(&mut self) {
+        self.deadline = Instant::now() + self.interval;
+    }
+
+    /// Waits for the next frame deadline and returns how many frames are due.
+    pub fn wait(&mut self) -> u32 {
+        self.wait_on(&mut Os)
     }
 
-    pub fn stop_and_wait(&mut self) {
-        self.end_time = Instant::now();
-        let elapsed = self.end_time - self.start_time;
-        if elapsed < INTERVAL {
-            sleep(INTERVAL - elapsed);
-            self.end_time += INTERVAL;
+    fn wait_on(&mut self, time: &mut impl Time) -> u32 {
+        while let Some(left) = self.deadline.checked_duration_since(time.now()) {
+            if left > SPIN {
+                time.sleep(left - SPIN);
+            } else {
+                time.spin();
+            }
+        }
+
+        self.due(time.now(), Duration::ZERO)
+    }
+
+    /// The frames due at `now` when the ticks come from elsewhere (the display, the audio
+    /// device). A tick up to half a frame early still counts, so a display refreshing at
+    /// about 60 Hz runs exactly one frame per refresh despite the jitter.
+    pub fn tick(&mut self, now: Instant) -> u32 {
+        self.due(now, self.interval / 2)
+    }
+
+    fn due(&mut self, now: Instant, tolerance: Duration) -> u32 {
+        let Some(late) = (now + tolerance).checked_duration_since(self.deadline) else {
+            return 0;
+        };
+
+        let frames = (late.as_nanos() / self.interval.as_nanos()) as u32 + 1;
+        if frames > MAX_CATCH_UP {
+            self.deadline = now + self.interval;
+            MAX_CATCH_UP
         } else {
-            self.end_time = self.start_time;
+            self.deadline += self.interval * frames;
+            frames
+        }
+    }
+
+    /// Counts a frame that ran `instructions` instructions.
+    pub fn record(&mut self, instructions: u32) {
+        self.frames += 1;
+        self.instructions += u64::from(instructions);
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= RATE_WINDOW {
+            self.fps = f64::from(self.frames) / elapsed.as_secs_f64();
+            self.ips = self.instructions as f64 / elapsed.as_secs_f64();
+            self.window_start = Instant::now();
+            self.frames = 0;
+            self.instructions = 0;
+        }
+    }
+
+    /// Measured frames per second, refreshed every second.
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// Measured instructions per second, refreshed every second.
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPIN_STEP: Duration = Duration::from_micros(100);
+
+    /// A system waking the sleeping threads `late`, and spinning in steps of SPIN_STEP.
+    struct FakeTime {
+        now: Instant,
+        late: Duration,
+    }
+
+    impl Time for FakeTime {
+        fn now(&self) -> Instant {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.now += duration + self.late;
         }
+
+        fn spin(&mut self) {
+            self.now += SPIN_STEP;
+        }
+    }
+
+    #[test]
+    fn wait_keeps_the_average_rate() {
+        for late in [Duration::ZERO, Duration::from_micros(700), Duration::from_millis(3)] {
+            let mut clock = Clock::new();
+            let start = clock.deadline - INTERVAL;
+            let mut time = FakeTime { now: start, late };
+
+            let frames: u32 = (0..600).map(|_| clock.wait_on(&mut time)).sum();
+
+            // The late wake-ups do not add up: 600 frames take 10 s and the last wake-up.
+            assert_eq!(frames, 600, "{late:?}");
+            let elapsed = time.now - start;
+            assert!(elapsed >= INTERVAL * 600, "{late:?}: {elapsed:?}");
+            assert!(elapsed <= INTERVAL * 600 + late + SPIN_STEP, "{late:?}: {elapsed:?}");
+        }
+    }
+
+    #[test]
+    fn catch_up_is_bounded() {
+        let mut clock = Clock::new();
+        let start = clock.deadline;
+
+        assert_eq!(clock.due(start - INTERVAL / 2, Duration::ZERO), 0);
+        assert_eq!(clock.due(start, Duration::ZERO), 1);
+        assert_eq!(clock.due(start + INTERVAL * 2, Duration::ZERO), 2);
+        assert_eq!(clock.deadline, start + INTERVAL * 3);
+
+        let late = start + INTERVAL * 100;
+        assert_eq!(clock.due(late, Duration::ZERO), MAX_CATCH_UP);
+        assert_eq!(clock.deadline, late + INTERVAL);
+    }
+
+    #[test]
+    fn early_ticks_count_within_half_a_frame() {
+        let mut clock = Clock::new();
+        let start = clock.deadline;
+
+        assert_eq!(clock.tick(start - INTERVAL / 4), 1);
+        assert_eq!(clock.tick(start + INTERVAL - INTERVAL / 4), 1);
+        assert_eq!(clock.tick(start + INTERVAL + INTERVAL / 4), 0);
+    }
+
+    #[test]
+    fn record_measures_the_rates() {
+        let mut clock = Clock::new();
+        clock.window_start -= RATE_WINDOW * 2;
+
+        clock.record(10);
+
+        assert!(clock.fps() > 0.4 && clock.fps() <= 0.5);
+        assert!(clock.ips() > 4.0 && clock.ips() <= 5.0);
     }
 }
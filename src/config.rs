@@ -1,3 +1,7 @@
 pub const W: usize = 64;
 pub const H: usize = 32;
-pub const WXH: usize = W * H;
\ No newline at end of file
+/// The height of the hi-res variant of the VIP interpreter.
+pub const HIRES_H: usize = 64;
+/// The high resolution display of SUPER-CHIP, 00FF switches to it.
+pub const SCHIP_W: usize = 128;
+pub const SCHIP_H: usize = 64;
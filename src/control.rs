@@ -0,0 +1,49 @@
+use crate::chip8x::ColourBoard;
+use crate::clock::Sync;
+use crate::frame_buffer::FrameBuffer;
+use std::path::PathBuf;
+
+/// Commands sent by the frontend to the CPU thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Stop the emulation and end the CPU thread.
+    Quit,
+    Pause,
+    Resume,
+    /// Restart the current ROM from a clean machine.
+    Reset,
+    /// Replace the running ROM.
+    LoadRom(PathBuf),
+    /// Emulation speed in instructions per 60 Hz frame.
+    SetSpeed(u32),
+    /// Run as fast as possible (true) or at the set speed (false).
+    Turbo(bool),
+    /// Run a single frame while paused.
+    Step,
+    /// A CHIP-8 key went down (true) or up (false).
+    Key(u8, bool),
+    /// A key of the second CHIP-8X keypad went down (true) or up (false).
+    Key2(u8, bool),
+    /// The frontend presented a frame (Vsync) or the audio device played one (Audio).
+    Tick(Sync),
+}
+
+/// Status reports sent by the CPU thread to the frontend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// The vram changed.
+    Frame(FrameBuffer),
+    /// The RGBA pixels of the MegaChip display, sent when 00E0 shows them. None when the
+    /// CHIP-8 display is shown again, after 0010 or a reset.
+    TrueColour(Option<Box<[u8]>>),
+    /// Something went wrong, the emulation goes on if it can.
+    Error(String),
+    /// The CPU stopped and waits for a Reset, a LoadRom or a Quit.
+    Halted,
+    /// The speed changed to the one the ROM database has for the ROM.
+    Speed(u32),
+    /// The display is this wide and high, in pixels.
+    Resolution(usize, usize),
+    /// The CHIP-8X colours changed, `None` for the palette colours of the other platforms.
+    Colours(Option<Box<ColourBoard>>),
+}
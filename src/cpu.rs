@@ -1,14 +1,26 @@
-use std::{fs, io};
-use std::process::exit;
-use crate::config::WXH;
+use std::io;
+use std::ops::Range;
+use crate::chip8x::ColourBoard;
+use crate::config::{H, HIRES_H, W};
+use crate::frame_buffer::FrameBuffer;
+use crate::instruction::Instruction;
+use crate::translator::Blocks;
+use crate::megachip::MegaChip;
+use sha1::{Digest, Sha1};
 
 pub(crate) const MEMORY_SIZE: usize = 4096;
 pub(crate) const START_ADDRESS: usize = 0x200;
 
 pub struct Cpu {
-    pub mem: [u8; MEMORY_SIZE],
+    /// MEMORY_SIZE bytes, more for MegaChip whose I reaches 16 MiB.
+    pub mem: Vec<u8>,
+    /// The instruction decoded at every address below MEMORY_SIZE, with its opcode, until the
+    /// memory under it is written. See CpuCore::tick.
+    pub decoded: Vec<Option<(Instruction, u16)>>,
+    /// The straight-line code translated, see CpuCore::run_block.
+    pub(crate) blocks: Blocks,
     pub v: [u8; 16],
-    pub i: u16,
+    pub i: u32,
     pub pc: u16,
     pub sp: u8,
     pub stack: [u16; 256],
@@ -16,12 +28,27 @@ pub struct Cpu {
     pub sound_timer: u8,
     pub wait_for_key: bool,
     pub keypad: [bool; 16],
-    pub vram: [u8; WXH],
+    /// The second keypad of CHIP-8X.
+    pub keypad2: [bool; 16],
+    pub vram: FrameBuffer,
+    /// The colours of CHIP-8X.
+    pub colours: ColourBoard,
+    /// The pitch CHIP-8X set with FxF8, see chip8x::frequency.
+    pub tone: Option<u8>,
+    /// The MegaChip display and sound, on the MegaChip platform.
+    pub mega: Option<Box<MegaChip>>,
+    /// The SUPER-CHIP flag registers, Fx75 saves V0 to Vx in them and Fx85 loads them back.
+    pub flags: [u8; 16],
     pub draw_flag: bool,
     pub running: bool,
     pub panic: bool,
 }
 
+/// The hi-res variant of the VIP interpreter runs the programs starting with a 1260 jump...
+pub(crate) const HIRES_SIGNATURE: [u8; 2] = [0x12, 0x60];
+/// ...from here.
+pub(crate) const HIRES_START: u16 = 0x244;
+
 pub(crate) const FONT_ADDR: u16 = 0x00;
 pub(crate) const FONT_SIZE: u16 = 5;
 pub(crate) const FONT: [u8; 80] = [
@@ -43,6 +70,22 @@ pub(crate) const FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80,
 ];
 
+/// The 8x10 digits of SUPER-CHIP, Fx30 points I at them. See Cpu::load_big_font.
+pub(crate) const BIG_FONT_ADDR: u16 = 0x50;
+pub(crate) const BIG_FONT_SIZE: u16 = 10;
+pub(crate) const BIG_FONT: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C,
+];
+
 const PANIC_0XID8: [u8; 256] = [
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
     0xff, 0x81, 0xff, 0xfe, 0x60, 0x1f, 0x81, 0xff,
@@ -78,10 +121,19 @@ const PANIC_0XID8: [u8; 256] = [
     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
 ];
 
+/// The SHA-1 of a ROM, hex encoded, the key of the ROM database.
+pub fn sha1(rom_data: &[u8]) -> String {
+    let sha1 = Sha1::digest(rom_data);
+    sha1.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 impl Cpu {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let mut s = Self {
-            mem: [0; MEMORY_SIZE],
+            mem: vec![0; MEMORY_SIZE],
+            decoded: vec![None; MEMORY_SIZE],
+            blocks: Blocks::new(),
             v: [0; 16],
             i: 0,
             pc: START_ADDRESS as u16,
@@ -90,8 +142,13 @@ impl Cpu {
             delay_timer: 0,
             sound_timer: 0,
             keypad: [false; 16],
+            keypad2: [false; 16],
             wait_for_key: false,
-            vram: [0; WXH],
+            vram: FrameBuffer::new(W, H, 1),
+            colours: ColourBoard::default(),
+            tone: None,
+            mega: None,
+            flags: [0; 16],
             draw_flag: false,
             running: true,
             panic: false,
@@ -109,36 +166,78 @@ impl Cpu {
         self.mem[80..512].fill(0);
     }
 
+    /// Copies the big digits after the small ones, for the platforms with Fx30.
+    pub(crate) fn load_big_font(&mut self) {
+        let start = usize::from(BIG_FONT_ADDR);
+        self.mem[start..start + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+    }
+
+    /// The display height in pixels.
+    pub fn height(&self) -> usize {
+        self.vram.height()
+    }
+
+    /// Runs on the 64x64 display of the hi-res variant.
+    pub fn set_hires(&mut self) {
+        self.set_resolution(W, HIRES_H);
+    }
+
+    /// Switches to a `width` x `height` display, which is cleared.
+    pub fn set_resolution(&mut self, width: usize, height: usize) {
+        self.vram.resize(width, height);
+        // 0230 clears the hi-res display only.
+        self.invalidate_all();
+    }
+
+    /// Forgets the instructions decoded over the bytes `addrs` that were written, the one
+    /// starting on the byte before included, and the blocks over them.
+    pub fn invalidate(&mut self, addrs: Range<usize>) {
+        self.blocks.invalidate(addrs.clone());
+        let end = addrs.end.min(self.decoded.len());
+        let start = addrs.start.saturating_sub(1).min(end);
+        self.decoded[start..end].fill(None);
+    }
+
+    /// Forgets every instruction decoded, when the memory or the mode changes all at once.
+    pub fn invalidate_all(&mut self) {
+        self.decoded.fill(None);
+        self.blocks.clear();
+    }
+
+    pub fn hires(&self) -> bool {
+        (self.vram.width(), self.vram.height()) == (W, HIRES_H)
+    }
+
     pub fn panic(&mut self) {
-        let mut counter = 0;
-        for byte in PANIC_0XID8 {
+        for (byte, &bits) in PANIC_0XID8.iter().enumerate() {
             for i in 0..8 {
-                let bit = (byte >> (7 - i)) & 1;
-                self.vram[counter + i] = if bit == 1 { 0xFF } else { 0x00 };
+                let (x, y) = (byte * 8 % W + i, byte * 8 / W);
+                self.vram.set(x, y, (bits >> (7 - i)) & 1);
             }
-            counter += 8;
         }
         self.panic = true;
     }
 
-    #[allow(dead_code)]
-    pub fn load_rom(&mut self, filename: &str) -> Result<(), io::Error> {
-        let rom_data = fs::read(filename)?;
+    /// Copies the ROM at `start`, where the program counter is set, and returns its SHA-1.
+    /// See Platform::start_address for where, rom_file for reading ROMs.
+    pub fn load_rom_at(&mut self, rom_data: &[u8], start: usize) -> Result<String, io::Error> {
 
         for (i, &byte) in rom_data.iter().enumerate() {
-            if crate::cpu_core::START_ADDRESS + i >= crate::cpu_core::MEMORY_SIZE {
-                eprintln!(
-                    "Buffer overflow.\nThe file is overflowing the available memory\nExiting"
-                );
-                exit(1);
+            if start + i >= self.mem.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the file is overflowing the available memory",
+                ));
             }
 
-            self.mem[crate::cpu_core::START_ADDRESS + i] = byte;
+            self.mem[start + i] = byte;
         }
+        self.invalidate_all();
+        self.pc = start as u16;
 
         self.panic = false;
 
-        Ok(())
+        Ok(sha1(rom_data))
     }
 
 }
@@ -146,7 +245,8 @@ impl Cpu {
 
 #[cfg(test)]
 mod tests {
-    use crate::cpu::{Cpu, FONT};
+    use crate::cpu::{Cpu, FONT, START_ADDRESS};
+    use std::fs;
 
     #[test]
     fn test_reset_memory() {
@@ -154,7 +254,7 @@ mod tests {
 
         cpu.reset_memory();
 
-        assert!(cpu.mem.iter().nth(80).iter().all(|&&b| b == 0));
+        assert_eq!(cpu.mem[80], 0);
         assert_eq!(cpu.mem[0], 0xf0);
         assert_eq!(cpu.mem[1], 0x90);
         assert_eq!(cpu.mem[77], 0xF0);
@@ -164,4 +264,12 @@ mod tests {
             println!("{:08b} - {}", &byte, i);
         }
     }
+
+    #[test]
+    fn load_rom_returns_the_sha1() {
+        let mut cpu = Cpu::new();
+
+        let sha1 = cpu.load_rom_at(&fs::read("tests/2-ibm-logo.ch8").unwrap(), START_ADDRESS).unwrap();
+        assert_eq!(sha1, "b9bbc12cee3f7b9d3b1f69161f7d7a2d86953379");
+    }
 }
@@ -1,29 +1,52 @@
-use crate::config::WXH;
+use crate::control::Status;
 use crate::cpu::Cpu;
+use crate::instruction::Instruction;
+use crate::profiler::Profiler;
+use crate::quirks::{Platform, Quirks};
 use std::sync::mpsc::Sender;
 
-pub(crate) const MEMORY_SIZE: usize = 4096;
-pub(crate) const START_ADDRESS: usize = 0x200;
+pub const MEMORY_SIZE: usize = 4096;
+pub const START_ADDRESS: usize = 0x200;
+
+/// An op_ function, taking the opcode apart itself.
+pub type Handler = fn(&mut CpuCore, &mut Cpu, u16);
 
 pub struct CpuCore {
-    tx: Option<Sender<[u8; WXH]>>,
+    tx: Option<Sender<Status>>,
+    pub(crate) quirks: Quirks,
+    /// The SUPER-CHIP, XO-CHIP and CHIP-8X instructions are decoded on their platforms only.
+    pub platform: Platform,
+    /// Set by Dxyn with the vblank quirk: the frame ends there.
+    pub(crate) wait_vblank: bool,
+    /// An unsupported opcode was reported to the frontend.
+    unknown_reported: bool,
+    /// Counts the instructions run, with `--profile`.
+    pub(crate) profiler: Option<Profiler>,
 }
 
 impl CpuCore {
-    pub(crate) fn new(tx: Option<Sender<[u8; WXH]>>) -> Self {
+    pub fn new(tx: Option<Sender<Status>>) -> Self {
         Self {
-            tx
+            tx,
+            quirks: Quirks::original(),
+            platform: Platform::default(),
+            wait_vblank: false,
+            unknown_reported: false,
+            profiler: None,
         }
     }
 
+    /// Runs an instruction. The display is sent by the emulator once a frame, when it changed.
+    /// The word at the program counter is decoded the first time it runs, see Cpu::decoded.
     pub fn tick(&mut self, cpu: &mut Cpu) {
         cpu.draw_flag = false;
 
-        let opcode = self.fetch_opcode(cpu);
-        self.decode_opcode(cpu, opcode);
-
-        if cpu.draw_flag {
-            self.tx.as_ref().map(|x| { x.send(cpu.vram) });
+        match cpu.decoded[usize::from(cpu.pc)] {
+            Some((instruction, opcode)) => self.execute(cpu, instruction, opcode),
+            None => {
+                let opcode = self.fetch_opcode(cpu);
+                self.decode_opcode(cpu, opcode);
+            }
         }
 
         // print!("{:04X} ", opcode);
@@ -36,20 +59,26 @@ impl CpuCore {
         // }
         // println!();
 
+        if !cpu.wait_for_key {
+            cpu.pc = (cpu.pc + 2) % MEMORY_SIZE as u16;
+        }
+    }
+
+    pub(crate) fn send(&self, status: Status) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(status);
+        }
+    }
+
+    /// Counts the delay and sound timers down, to be called at 60 Hz whatever the speed is.
+    pub fn tick_timers(&mut self, cpu: &mut Cpu) {
         if cpu.delay_timer > 0 {
             cpu.delay_timer -= 1;
         }
 
+        // The buzzer sounds while the sound timer runs, see Beeper.
         if cpu.sound_timer > 0 {
             cpu.sound_timer -= 1;
-            if cpu.sound_timer == 0 {
-                // Placeholder for actual audio.
-                println!("beep!");
-            }
-        }
-
-        if !cpu.wait_for_key {
-            cpu.pc = (cpu.pc + 2) % MEMORY_SIZE as u16;
         }
     }
 
@@ -64,77 +93,255 @@ impl CpuCore {
     }
 
     /// Chip 8 - Instruction set
-    pub(super) fn decode_opcode(&mut self, cpu: &mut Cpu, opcode: u16) {
+    /// What `opcode` runs on the platform and in the mode of `cpu`, see Instruction.
+    pub(crate) fn decode(&self, cpu: &Cpu, opcode: u16) -> Instruction {
+        let chip8x = self.platform == Platform::Chip8x;
+        let mega = cpu.mega.as_ref().is_some_and(|m| m.enabled);
+        let schip = matches!(self.platform, Platform::Schip | Platform::Xochip | Platform::Megachip);
+        let xochip = self.platform == Platform::Xochip;
+
         match opcode {
+            // 0x00cn - SUPER-CHIP Scroll the display down n lines.
+            0x00C1..=0x00CF if schip => Instruction::ScrollDown,
+            // 0x00dn - XO-CHIP Scroll the display up n lines.
+            0x00D1..=0x00DF if xochip => Instruction::ScrollUp,
+            // 0x00fb / 0x00fc - SUPER-CHIP Scroll the display 4 pixels right / left.
+            0x00FB if schip => Instruction::ScrollRight,
+            0x00FC if schip => Instruction::ScrollLeft,
+            // 0x00fd - SUPER-CHIP Exit the interpreter.
+            0x00FD if schip => Instruction::Exit,
+            // 0x00fe / 0x00ff - SUPER-CHIP Switch to the low / high resolution.
+            0x00FE if schip => Instruction::Low,
+            0x00FF if schip => Instruction::High,
+            // 0x0010 / 0x0011 - MegaChip Leave / enter the MegaChip mode.
+            0x0010 | 0x0011 if cpu.mega.is_some() => Instruction::MegaMode,
+            // 0x00e0 - MegaChip Show the frame drawn and start a new one.
+            0x00e0 if mega => Instruction::MegaCls,
             // 0x00e0 - CLS Clear display
-            0x00e0 => self.op_00e0(cpu, opcode),
+            0x00e0 => Instruction::Cls,
             // 0x00d4 - RET Return from a machine language subroutine.
-            0x00d4 => self.op_00d4(cpu, opcode),
+            0x00d4 => Instruction::MachineRet,
+            // 0x0230 - CLS Clear the hi-res display.
+            0x0230 if cpu.hires() => Instruction::Cls,
             // 0x00ee - RET Return from a subroutine.
-            0x00ee => self.op_00ee(cpu, opcode),
-            // 0x0nnn - SYS Ignored (old SYS addr)
-            0x0000..=0x0FFF => self.op_0nnn(cpu, opcode),
+            0x00ee => Instruction::Ret,
+            // 0x02a0 - CHIP-8X Cycle the background colour.
+            0x02a0 if chip8x => Instruction::Background,
+            // 0x01nn nnnn - MegaChip Set I to a 24-bit address.
+            0x0100..=0x01FF if mega => Instruction::LdILong,
+            // 0x02nn - MegaChip Load nn palette colours from I.
+            0x0200..=0x02FF if mega => Instruction::Palette,
+            // 0x03nn / 0x04nn - MegaChip Set the sprite width / height.
+            0x0300..=0x03FF if mega => Instruction::SpriteWidth,
+            0x0400..=0x04FF if mega => Instruction::SpriteHeight,
+            // 0x05nn - MegaChip Set the sprite alpha.
+            0x0500..=0x05FF if mega => Instruction::Alpha,
+            // 0x060n / 0x0700 - MegaChip Play / stop the sample at I.
+            0x0600..=0x060F if mega => Instruction::PlaySample,
+            0x0700 if mega => Instruction::StopSample,
+            // 0x080n - MegaChip Set the blend mode.
+            0x0800..=0x080F if mega => Instruction::Blend,
+            // 0x09nn - MegaChip Set the collision colour.
+            0x0900..=0x09FF if mega => Instruction::Collision,
+            // 0x0nnn - SYS Call a machine code routine.
+            0x0000..=0x0FFF => Instruction::Sys,
             // 0x1nnn - JP addr
-            0x1000..=0x1FFF => self.op_1nnn(cpu, opcode),
+            0x1000..=0x1FFF => Instruction::Jp,
             // 0x2nnn - CALL addr
-            0x2000..=0x2FFF => self.op_2nnn(cpu, opcode),
+            0x2000..=0x2FFF => Instruction::Call,
             // 0x3xkk - Skip next instruction if Vx = kk.
-            0x3000..=0x3FFF => self.op_3xkk(cpu, opcode),
+            0x3000..=0x3FFF => Instruction::SeByte,
             // 0x4xkk - Skip next instruction if Vx != kk.
-            0x4000..=0x4FFF => self.op_4xkk(cpu, opcode),
+            0x4000..=0x4FFF => Instruction::SneByte,
             // 0x5xy0 - Skip next instruction if Vx = Vy.
-            code if code & 0xF00F == 0x5000 => self.op_5xy0(cpu, opcode),
+            code if code & 0xF00F == 0x5000 => Instruction::SeReg,
+            // 0x5xy1 - CHIP-8X Add Vy to Vx nibble by nibble.
+            code if chip8x && code & 0xF00F == 0x5001 => Instruction::AddNibbles,
+            // 0x5xy2 / 0x5xy3 - XO-CHIP Save / load Vx to Vy at I.
+            code if xochip && code & 0xF00F == 0x5002 => Instruction::SaveRange,
+            code if xochip && code & 0xF00F == 0x5003 => Instruction::LoadRange,
             // 6xkk - Set Vx = kk.
-            0x6000..=0x6FFF => self.op_6xkk(cpu, opcode),
+            0x6000..=0x6FFF => Instruction::LdByte,
             // 7xkk - Set Vx = Vx + kk.
-            0x7000..=0x7FFF => self.op_7xkk(cpu, opcode),
+            0x7000..=0x7FFF => Instruction::AddByte,
             // 0x9xy0 - Skip next instruction if Vx != Vy.
-            code if code & 0xF00F == 0x9000 => self.op_9xy0(cpu, opcode),
+            code if code & 0xF00F == 0x9000 => Instruction::SneReg,
             // 0xAnnn - The value of register I is set to nnn.
-            0xA000..=0xAFFF => self.op_annn(cpu, opcode),
+            0xA000..=0xAFFF => Instruction::LdI,
             // 0xBnnn - Jump to location nnn + V0.
-            0xB000..=0xBFFF => self.op_bnnn(cpu, opcode),
+            // 0xbxyn - CHIP-8X Set the foreground colour.
+            0xB000..=0xBFFF if chip8x => Instruction::Colour,
+            0xB000..=0xBFFF => Instruction::JpV0,
             // 0xcxkk - Set Vx = random byte AND kk.
-            0xC000..=0xCFFF => self.op_cxkk(cpu, opcode),
+            0xC000..=0xCFFF => Instruction::Rnd,
+            // 0xdxyn - MegaChip Draw the palette sprite at I at (Vx, Vy).
+            0xD000..=0xDFFF if mega => Instruction::MegaDrw,
             // 0xdxyn - Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-            0xD000..=0xDFFF => self.op_dxyn(cpu, opcode),
+            0xD000..=0xDFFF => Instruction::Drw,
             // 0xex9e - Checks the keyboard
-            code if code & 0xF0FF == 0xE09E => self.op_ex9e(cpu, opcode),
+            code if code & 0xF0FF == 0xE09E => Instruction::Skp,
             // 0xexa1 - Checks the keyboard
-            code if code & 0xF0FF == 0xE0A1 => self.op_exa1(cpu, opcode),
-            code if code & 0xF0FF == 0xF007 => self.op_fx07(cpu, opcode),
-            code if code & 0xF0FF == 0xF00A => self.op_fx0a(cpu, opcode),
-            code if code & 0xF0FF == 0xF015 => self.op_fx15(cpu, opcode),
-            code if code & 0xF0FF == 0xF018 => self.op_fx18(cpu, opcode),
-            code if code & 0xF0FF == 0xF01E => self.op_fx1e(cpu, opcode),
-            code if code & 0xF0FF == 0xF029 => self.op_fx29(cpu, opcode),
-            code if code & 0xF0FF == 0xF033 => self.op_fx33(cpu, opcode),
-            code if code & 0xF0FF == 0xF055 => self.op_fx55(cpu, opcode),
-            code if code & 0xF0FF == 0xF065 => self.op_fx65(cpu, opcode),
+            code if code & 0xF0FF == 0xE0A1 => Instruction::Sknp,
+            // 0xexf2 / 0xexf5 - CHIP-8X Checks the second keyboard
+            code if chip8x && code & 0xF0FF == 0xE0F2 => Instruction::Skp2,
+            code if chip8x && code & 0xF0FF == 0xE0F5 => Instruction::Sknp2,
+            code if code & 0xF0FF == 0xF007 => Instruction::LdVxDt,
+            code if code & 0xF0FF == 0xF00A => Instruction::LdVxK,
+            code if code & 0xF0FF == 0xF015 => Instruction::LdDtVx,
+            code if code & 0xF0FF == 0xF018 => Instruction::LdStVx,
+            code if code & 0xF0FF == 0xF01E => Instruction::AddIVx,
+            code if code & 0xF0FF == 0xF029 => Instruction::LdF,
+            code if code & 0xF0FF == 0xF033 => Instruction::LdB,
+            code if code & 0xF0FF == 0xF055 => Instruction::Store,
+            code if code & 0xF0FF == 0xF065 => Instruction::Load,
+            // 0xfxf8 - CHIP-8X Sets the pitch
+            code if chip8x && code & 0xF0FF == 0xF0F8 => Instruction::Pitch,
+            // 0xfx30 - SUPER-CHIP Point I at the big font digit Vx.
+            code if schip && code & 0xF0FF == 0xF030 => Instruction::LdHf,
+            // 0xfx75 / 0xfx85 - SUPER-CHIP Save / load V0 to Vx in the flag registers.
+            code if schip && code & 0xF0FF == 0xF075 => Instruction::SaveFlags,
+            code if schip && code & 0xF0FF == 0xF085 => Instruction::LoadFlags,
+            // 0xf000 nnnn - XO-CHIP Set I to the next word.
+            0xF000 if xochip => Instruction::LdIWord,
+            // 0xfn01 - XO-CHIP Select the drawing planes.
+            code if xochip && code & 0xF0FF == 0xF001 => Instruction::Plane,
+            // 0xf002 - XO-CHIP Load the audio pattern at I.
+            0xF002 if xochip => Instruction::Audio,
+            // 0xfx3a - XO-CHIP Set the audio pitch.
+            code if xochip && code & 0xF0FF == 0xF03A => Instruction::AudioPitch,
             // 0x8xy0-0x8xyE - Arithmetic/logic operations
             code => match code & 0xF00F {
                 // 0x8xy0 - Set Vx = Vy.
-                0x8000 => self.op_8xy0(cpu, opcode),
+                0x8000 => Instruction::LdReg,
                 // 0x8xy1 - Set Vx = Vy.
-                0x8001 => self.op_8xy1(cpu, opcode),
+                0x8001 => Instruction::Or,
                 // 0x8xy2 - Set Vx = Vy.
-                0x8002 => self.op_8xy2(cpu, opcode),
+                0x8002 => Instruction::And,
                 // 0x8xy3 - Set Vx = Vy.
-                0x8003 => self.op_8xy3(cpu, opcode),
+                0x8003 => Instruction::Xor,
                 // 0x8xy4 - Set Vx = Vy.
-                0x8004 => self.op_8xy4(cpu, opcode),
+                0x8004 => Instruction::AddReg,
                 // 0x8xy5 - Set Vx = Vy.
-                0x8005 => self.op_8xy5(cpu, opcode),
+                0x8005 => Instruction::Sub,
                 // 0x8xy6 - Set Vx = Vy.
-                0x8006 => self.op_8xy6(cpu, opcode),
+                0x8006 => Instruction::Shr,
                 // 0x8xy7 - Set Vx = Vy.
-                0x8007 => self.op_8xy7(cpu, opcode),
+                0x8007 => Instruction::Subn,
                 // 0x8xye - Set Vx = Vy.
-                0x800e => self.op_8xye(cpu, opcode),
-                _ => println!("Not matching"),
+                0x800e => Instruction::Shl,
+                _ => Instruction::Unknown,
             },
         }
     }
+
+    /// Decodes and runs `opcode`, the one at the program counter, which keeps it decoded.
+    pub(super) fn decode_opcode(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let instruction = self.decode(cpu, opcode);
+        cpu.decoded[usize::from(cpu.pc)] = Some((instruction, opcode));
+        self.execute(cpu, instruction, opcode);
+    }
+
+    /// Runs `opcode`, decoded as `instruction`.
+    fn execute(&mut self, cpu: &mut Cpu, instruction: Instruction, opcode: u16) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(cpu, instruction, opcode);
+        }
+        Self::handler(instruction)(self, cpu, opcode);
+    }
+
+    /// The op_ function running `instruction`.
+    pub fn handler(instruction: Instruction) -> Handler {
+        match instruction {
+            Instruction::MegaMode => Self::op_001n,
+            Instruction::MegaCls => Self::op_00e0_mega,
+            Instruction::Cls => Self::op_00e0,
+            Instruction::MachineRet => Self::op_00d4,
+            Instruction::Ret => Self::op_00ee,
+            Instruction::Background => Self::op_02a0,
+            Instruction::LdILong => Self::op_01nn,
+            Instruction::Palette => Self::op_02nn,
+            Instruction::SpriteWidth => Self::op_03nn,
+            Instruction::SpriteHeight => Self::op_04nn,
+            Instruction::Alpha => Self::op_05nn,
+            Instruction::PlaySample => Self::op_060n,
+            Instruction::StopSample => Self::op_0700,
+            Instruction::Blend => Self::op_080n,
+            Instruction::Collision => Self::op_09nn,
+            Instruction::Sys => Self::op_0nnn,
+            Instruction::Jp => Self::op_1nnn,
+            Instruction::Call => Self::op_2nnn,
+            Instruction::SeByte => Self::op_3xkk,
+            Instruction::SneByte => Self::op_4xkk,
+            Instruction::SeReg => Self::op_5xy0,
+            Instruction::AddNibbles => Self::op_5xy1,
+            Instruction::LdByte => Self::op_6xkk,
+            Instruction::AddByte => Self::op_7xkk,
+            Instruction::SneReg => Self::op_9xy0,
+            Instruction::LdI => Self::op_annn,
+            Instruction::Colour => Self::op_bxyn,
+            Instruction::JpV0 => Self::op_bnnn,
+            Instruction::Rnd => Self::op_cxkk,
+            Instruction::MegaDrw => Self::op_dxyn_mega,
+            Instruction::Drw => Self::op_dxyn,
+            Instruction::Skp => Self::op_ex9e,
+            Instruction::Sknp => Self::op_exa1,
+            Instruction::Skp2 => Self::op_exf2,
+            Instruction::Sknp2 => Self::op_exf5,
+            Instruction::LdVxDt => Self::op_fx07,
+            Instruction::LdVxK => Self::op_fx0a,
+            Instruction::LdDtVx => Self::op_fx15,
+            Instruction::LdStVx => Self::op_fx18,
+            Instruction::AddIVx => Self::op_fx1e,
+            Instruction::LdF => Self::op_fx29,
+            Instruction::LdB => Self::op_fx33,
+            Instruction::Store => Self::op_fx55,
+            Instruction::Load => Self::op_fx65,
+            Instruction::Pitch => Self::op_fxf8,
+            Instruction::LdReg => Self::op_8xy0,
+            Instruction::Or => Self::op_8xy1,
+            Instruction::And => Self::op_8xy2,
+            Instruction::Xor => Self::op_8xy3,
+            Instruction::AddReg => Self::op_8xy4,
+            Instruction::Sub => Self::op_8xy5,
+            Instruction::Shr => Self::op_8xy6,
+            Instruction::Subn => Self::op_8xy7,
+            Instruction::Shl => Self::op_8xye,
+            Instruction::ScrollDown => Self::op_00cn,
+            Instruction::ScrollUp => Self::op_00dn,
+            Instruction::ScrollRight => Self::op_00fb,
+            Instruction::ScrollLeft => Self::op_00fc,
+            Instruction::Exit => Self::op_00fd,
+            Instruction::Low => Self::op_00fe,
+            Instruction::High => Self::op_00ff,
+            Instruction::LdHf => Self::op_fx30,
+            Instruction::SaveFlags => Self::op_fx75,
+            Instruction::LoadFlags => Self::op_fx85,
+            Instruction::LdIWord => Self::op_f000,
+            // Decoded for the analysis and the disassembler, not run.
+            Instruction::SaveRange
+            | Instruction::LoadRange
+            | Instruction::Plane
+            | Instruction::Audio
+            | Instruction::AudioPitch
+            | Instruction::Unknown => Self::op_unknown,
+        }
+    }
+
+    /// Tells the frontend about the first opcode that cannot run, the next ones are skipped
+    /// silently.
+    fn op_unknown(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if !self.unknown_reported {
+            self.unknown_reported = true;
+            self.send(Status::Error(format!("Unsupported opcode {opcode:04X} at {:03X}", cpu.pc)));
+        }
+    }
+
+    /// XO-CHIP F000 nnnn: the 16-bit addresses are not emulated, the address word is skipped
+    /// so that it does not run as an opcode.
+    fn op_f000(&mut self, cpu: &mut Cpu, opcode: u16) {
+        self.op_unknown(cpu, opcode);
+        cpu.pc = (cpu.pc + 2) % MEMORY_SIZE as u16;
+    }
 }
 
 #[cfg(test)]
@@ -146,7 +353,7 @@ mod tests {
     fn load_rom_test() {
         let mut cpu = Cpu::new();
 
-        cpu.load_rom("tests/fixtures/test_opcode.ch8")
+        cpu.load_rom_at(&std::fs::read("tests/fixtures/test_opcode.ch8").unwrap(), START_ADDRESS)
             .expect("Error loading fixture files");
 
         assert!(
@@ -165,7 +372,69 @@ mod tests {
         assert_eq!(cpu.i, 0);
         assert_eq!(cpu.sp, 0);
         assert!(cpu.stack.iter().all(|&s| s == 0));
-        assert!(cpu.vram.iter().all(|&p| p == 0));
+        assert!(cpu.vram.pixels().all(|p| p == 0));
         // assert!(cpu.mem.iter().all(|&b| b == 0));
     }
+
+    #[test]
+    fn unsupported_opcodes_are_reported_once() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cpu = Cpu::new();
+        let mut core = CpuCore::new(Some(tx));
+        core.platform = Platform::Xochip;
+        // F000 0123, then 8008 twice.
+        cpu.load_rom_at(&[0xF0, 0x00, 0x01, 0x23, 0x80, 0x08, 0x80, 0x08], START_ADDRESS).unwrap();
+
+        core.tick(&mut cpu);
+        assert_eq!(cpu.pc, 0x204);
+        core.tick(&mut cpu);
+        core.tick(&mut cpu);
+
+        let errors: Vec<_> = rx.try_iter().filter(|s| matches!(s, Status::Error(_))).collect();
+        assert_eq!(errors, [Status::Error("Unsupported opcode F000 at 200".into())]);
+    }
+
+    #[test]
+    fn stores_invalidate_the_decoded_instructions() {
+        let mut cpu = Cpu::new();
+        let mut core = CpuCore::new(None);
+        // 6001, then F155 and F233 writing over it.
+        cpu.load_rom_at(&[0x60, 0x01], START_ADDRESS).unwrap();
+        cpu.mem[0x300..0x304].copy_from_slice(&[0xF1, 0x55, 0xF2, 0x33]);
+
+        core.tick(&mut cpu);
+        assert_eq!(cpu.v[0], 1);
+        assert_eq!(cpu.decoded[START_ADDRESS], Some((Instruction::LdByte, 0x6001)));
+
+        // 6007 at 0200.
+        (cpu.i, cpu.v[0], cpu.v[1], cpu.pc) = (0x200, 0x60, 0x07, 0x300);
+        core.tick(&mut cpu);
+        cpu.pc = 0x200;
+        core.tick(&mut cpu);
+        assert_eq!(cpu.v[0], 7);
+
+        // 2, 5, 5 from 0201: the instruction at 0200 is 6002.
+        (cpu.i, cpu.v[2], cpu.pc) = (0x201, 255, 0x302);
+        core.tick(&mut cpu);
+        cpu.pc = 0x200;
+        core.tick(&mut cpu);
+        assert_eq!(cpu.v[0], 2);
+    }
+
+    #[test]
+    fn the_megachip_mode_decodes_again() {
+        let mut cpu = Cpu::new();
+        let mut core = CpuCore::new(None);
+        cpu.mega = Some(Box::default());
+        cpu.load_rom_at(&[0xD0, 0x01, 0x00, 0x11, 0x12, 0x00], START_ADDRESS).unwrap();
+
+        core.tick(&mut cpu);
+        assert_eq!(cpu.decoded[START_ADDRESS], Some((Instruction::Drw, 0xD001)));
+
+        core.tick(&mut cpu);
+        assert_eq!(cpu.decoded[START_ADDRESS], None);
+        core.tick(&mut cpu);
+        core.tick(&mut cpu);
+        assert_eq!(cpu.decoded[START_ADDRESS], Some((Instruction::MegaDrw, 0xD001)));
+    }
 }
@@ -1,4 +1,7 @@
+use crate::clock::Clock;
 use crate::cpu::Cpu;
+use crate::cpu_core::MEMORY_SIZE;
+use crate::profiler::{Profiler, READ, WRITTEN};
 use anyhow::Result;
 use crossterm::cursor::Show;
 use crossterm::event::{Event, KeyCode};
@@ -9,13 +12,17 @@ use ratatui::{
     widgets::{Block, Borders, Cell, Paragraph, Row, Table},
 };
 use std::io::{stdout, Stdout};
+use std::collections::VecDeque;
 use std::time::Duration;
-use std::{collections::VecDeque, time::Instant};
+
+const FPS_HISTORY: usize = 120;
+/// Memory bytes per heatmap cell and cells per line: 16 lines of 256 bytes.
+const HEAT_CELL: usize = 4;
+const HEAT_WIDTH: usize = 64;
 
 pub struct CpuDebugger {
     logs: Vec<String>,
     fps_history: VecDeque<f32>,
-    last_frame: Instant,
     terminal: Terminal<CrosstermBackend<Stdout>>,
 }
 
@@ -34,8 +41,7 @@ impl CpuDebugger {
 
         Self {
             logs: Vec::with_capacity(2000),
-            fps_history: VecDeque::with_capacity(120),
-            last_frame: Instant::now(),
+            fps_history: VecDeque::with_capacity(FPS_HISTORY),
             terminal,
         }
     }
@@ -48,7 +54,13 @@ impl CpuDebugger {
         }
     }
 
-    pub fn tick(&mut self, cpu: &mut Cpu) -> Result<()> {
+    /// Draws the panels, the Performance one with the rates measured by the clock and the
+    /// Heatmap one with the memory use when profiling.
+    pub fn tick(&mut self, cpu: &mut Cpu, clock: &Clock, profiler: Option<&Profiler>) -> Result<()> {
+        self.fps_history.push_front(clock.fps() as f32);
+        self.fps_history.truncate(FPS_HISTORY);
+        let avg_fps = self.avg_fps();
+
         // draw
         self.terminal.draw(|f| {
             let outer_layout = Layout::default()
@@ -75,7 +87,23 @@ impl CpuDebugger {
             let logs = Paragraph::new(log_text)
                 .block(Block::default().borders(Borders::ALL).title(" Logs "));
 
-            f.render_widget(logs, outer_layout[2]);
+            match profiler {
+                None => f.render_widget(logs, outer_layout[2]),
+                Some(profiler) => {
+                    let bottom_layout = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(vec![
+                            Constraint::Fill(1),
+                            Constraint::Length(HEAT_WIDTH as u16 + 6),
+                        ])
+                        .split(outer_layout[2]);
+
+                    let heatmap = Paragraph::new(heatmap(profiler))
+                        .block(Block::default().borders(Borders::ALL).title(" Heatmap "));
+                    f.render_widget(logs, bottom_layout[0]);
+                    f.render_widget(heatmap, bottom_layout[1]);
+                }
+            }
 
             let mut cpu_values: Vec<Row> = Vec::new();
             cpu_values.push(Row::new(vec![
@@ -84,11 +112,11 @@ impl CpuDebugger {
             ]));
             cpu_values.push(Row::new(vec![
                 Cell::from(" SP"), Cell::from(format!("{}", cpu.sp)),
-                Cell::from("DRAW"), Cell::from(format!("{}", if cpu.draw_flag { "□" } else { "■" })),
+                Cell::from("DRAW"), Cell::from(if cpu.draw_flag { "□" } else { "■" }),
             ]));
             cpu_values.push(Row::new(vec![
                 Cell::from(" I"), Cell::from(format!("{:#05X}", cpu.i)),
-                Cell::from("PANIC!"), Cell::from(format!("{}", if cpu.panic { "□" } else { "■" })),
+                Cell::from("PANIC!"), Cell::from(if cpu.panic { "□" } else { "■" }),
             ]));
 
             let cpu_var = Table::new(cpu_values, [
@@ -119,18 +147,11 @@ impl CpuDebugger {
             f.render_widget(regs, inner_layout[1]);
 
             // FPS
-            let mut avg = 0.0;
-
-            if !self.fps_history.is_empty() {
-                avg = self.fps_history.iter().copied().sum::<f32>() / (self.fps_history.len() as f32);
-            }
-
-            self.fps_history.push_front((1_000_000 / self.last_frame.elapsed().as_micros()) as f32);
-            self.last_frame = Instant::now();
             let fps_text = format!(
-                "FPS avg: {:>5.1}\nLast {} frame:\n{}",
-                avg,
-                self.fps_history.len(),
+                "FPS: {:>5.1}  IPS: {:>7.0}  avg FPS: {:>5.1}\n{}",
+                clock.fps(),
+                clock.ips(),
+                avg_fps,
                 ascii_sparkline(&self.fps_history, 10.0, 120.0)
             );
             let fps = Paragraph::new(fps_text)
@@ -139,11 +160,11 @@ impl CpuDebugger {
         })?;
 
         while event::poll(Duration::from_millis(0))? {
-            if let Event::Key(k) = event::read()? {
-                if k.code == KeyCode::Char('q') || k.code == KeyCode::Esc {
-                    cpu.running = false;
-                    break;
-                }
+            if let Event::Key(k) = event::read()?
+                && (k.code == KeyCode::Char('q') || k.code == KeyCode::Esc)
+            {
+                cpu.running = false;
+                break;
             }
         }
 
@@ -162,6 +183,38 @@ impl CpuDebugger {
     }
 }
 
+/// The memory, 4 bytes per cell: shades of red for the code by execution count, blue for
+/// the bytes written and green for the ones read.
+fn heatmap(profiler: &Profiler) -> Vec<Line<'static>> {
+    const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+    let max = (0..MEMORY_SIZE).map(|a| profiler.executions(a)).max().unwrap_or(0).max(2) as f64;
+
+    (0..MEMORY_SIZE)
+        .step_by(HEAT_CELL * HEAT_WIDTH)
+        .map(|row| {
+            let mut spans = vec![Span::raw(format!("{row:03X} "))];
+            for cell in (row..row + HEAT_CELL * HEAT_WIDTH).step_by(HEAT_CELL) {
+                let bytes = cell..cell + HEAT_CELL;
+                let executions = bytes.clone().map(|a| profiler.executions(a)).max().unwrap_or(0);
+                let access = bytes.fold(0, |access, a| access | profiler.access(a));
+
+                let span = if executions > 0 {
+                    let shade = (executions as f64).ln() / max.ln() * (SHADES.len() - 1) as f64;
+                    Span::styled(SHADES[shade.round() as usize].to_string(), Style::default().fg(Color::Red))
+                } else if access & WRITTEN != 0 {
+                    Span::styled("·", Style::default().fg(Color::Blue))
+                } else if access & READ != 0 {
+                    Span::styled("·", Style::default().fg(Color::Green))
+                } else {
+                    Span::styled("·", Style::default().fg(Color::DarkGray))
+                };
+                spans.push(span);
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
 fn ascii_sparkline(hist: &VecDeque<f32>, min: f32, max: f32) -> String {
     const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
     let mut s = String::new();
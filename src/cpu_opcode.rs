@@ -1,20 +1,36 @@
-use crate::config::{W, WXH};
-use crate::cpu::{Cpu, FONT_ADDR, FONT_SIZE};
-use crate::cpu_core::CpuCore;
+use crate::config::{H, SCHIP_H, SCHIP_W, W};
+use crate::cpu::{BIG_FONT_ADDR, BIG_FONT_SIZE, Cpu, FONT_ADDR, FONT_SIZE};
+use crate::control::Status;
+use crate::cpu_core::{CpuCore, MEMORY_SIZE};
+use crate::machine_code;
+use crate::megachip;
+use crate::quirks::Platform;
 use rand::random;
 
 impl CpuCore {
     /// 0nnn - SYS addr
-    /// Jump to a machine code routine at nnn.
+    /// Call the CDP1802 machine code routine at nnn.
     /// This instruction is only used on the old computers on which Chip-8 was
-    /// originally implemented. It is ignored by modern interpreters.
+    /// originally implemented. The routine runs as on the COSMAC VIP, see machine_code. On the
+    /// other platforms the program stops there, waiting.
     pub(super) fn op_0nnn(&mut self, cpu: &mut Cpu, opcode: u16) {
         let nnn = opcode & 0x0fff;
 
-        cpu.pc = nnn;
+        if !matches!(self.platform, Platform::Chip8 | Platform::Chip8x) && !cpu.hires() {
+            cpu.pc = nnn;
 
-        cpu.wait_for_key = true;
-        cpu.draw_flag = true;
+            cpu.wait_for_key = true;
+            cpu.draw_flag = true;
+            return;
+        }
+
+        let called = machine_code::call(cpu, nnn);
+        // The routine can write anywhere.
+        cpu.invalidate_all();
+        // The call is skipped, the program goes on.
+        if let Err(e) = called {
+            self.send(Status::Error(format!("{e:#}")));
+        }
     }
     /// 00D4 - RET from a machine language subroutine
     /// Return from a subroutine.
@@ -28,7 +44,7 @@ impl CpuCore {
     ///00E0 - CLS
     /// Clear the display.
     pub(super) fn op_00e0(&mut self, cpu: &mut Cpu, _opcode: u16) {
-        cpu.vram = [0; WXH];
+        cpu.vram.clear();
         cpu.draw_flag = true;
     }
 
@@ -41,6 +57,58 @@ impl CpuCore {
         cpu.sp -= 1;
     }
 
+    /// 00Cn - SCD n - SUPER-CHIP
+    /// Scroll the display down n lines.
+    pub(super) fn op_00cn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        cpu.vram.scroll_down(usize::from(opcode & 0xF));
+        cpu.draw_flag = true;
+    }
+
+    /// 00Dn - SCU n - XO-CHIP
+    /// Scroll the display up n lines.
+    pub(super) fn op_00dn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        cpu.vram.scroll_up(usize::from(opcode & 0xF));
+        cpu.draw_flag = true;
+    }
+
+    /// 00FB - SCR - SUPER-CHIP
+    /// Scroll the display 4 pixels right.
+    pub(super) fn op_00fb(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        cpu.vram.scroll_right(4);
+        cpu.draw_flag = true;
+    }
+
+    /// 00FC - SCL - SUPER-CHIP
+    /// Scroll the display 4 pixels left.
+    pub(super) fn op_00fc(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        cpu.vram.scroll_left(4);
+        cpu.draw_flag = true;
+    }
+
+    /// 00FD - EXIT - SUPER-CHIP
+    /// Stop the interpreter.
+    pub(super) fn op_00fd(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        cpu.running = false;
+    }
+
+    /// 00FE - LOW - SUPER-CHIP
+    /// Switch to the 64x32 display, which is cleared.
+    pub(super) fn op_00fe(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        self.switch_resolution(cpu, W, H);
+    }
+
+    /// 00FF - HIGH - SUPER-CHIP
+    /// Switch to the 128x64 display, which is cleared.
+    pub(super) fn op_00ff(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        self.switch_resolution(cpu, SCHIP_W, SCHIP_H);
+    }
+
+    fn switch_resolution(&self, cpu: &mut Cpu, width: usize, height: usize) {
+        cpu.set_resolution(width, height);
+        self.send(Status::Resolution(width, height));
+        cpu.draw_flag = true;
+    }
+
     /// Jump to location nnn.
     /// The interpreter sets the program counter to nnn.
     pub(super) fn op_1nnn(&mut self, cpu: &mut Cpu, opcode: u16) {
@@ -147,6 +215,9 @@ impl CpuCore {
         let (x, y) = Self::regs_xy(opcode);
 
         cpu.v[x] |= cpu.v[y];
+        if self.quirks.logic {
+            cpu.v[0xF] = 0;
+        }
     }
 
     /// 8xy2 - AND Vx, Vy
@@ -158,6 +229,9 @@ impl CpuCore {
         let (x, y) = Self::regs_xy(opcode);
 
         cpu.v[x] &= cpu.v[y];
+        if self.quirks.logic {
+            cpu.v[0xF] = 0;
+        }
     }
 
     /// 8xy3 - XOR Vx, Vy
@@ -168,7 +242,10 @@ impl CpuCore {
     pub(super) fn op_8xy3(&mut self, cpu: &mut Cpu, opcode: u16) {
         let (x, y) = Self::regs_xy(opcode);
 
-        cpu.v[x] ^= cpu.v[y]
+        cpu.v[x] ^= cpu.v[y];
+        if self.quirks.logic {
+            cpu.v[0xF] = 0;
+        }
     }
 
     /// 8xy4 - ADD Vx, Vy
@@ -219,9 +296,17 @@ impl CpuCore {
     pub(super) fn op_8xy6(&mut self, cpu: &mut Cpu, opcode: u16) {
         let (x, y) = Self::regs_xy(opcode);
 
-        cpu.v[x] = cpu.v[x] >> 1;
-        cpu.v[y] = cpu.v[x];
-        cpu.v[0xF] = cpu.v[x] & 0x1;
+        if self.quirks.legacy_shift {
+            cpu.v[x] >>= 1;
+            cpu.v[y] = cpu.v[x];
+            cpu.v[0xF] = cpu.v[x] & 0x1;
+            return;
+        }
+
+        let value = if self.quirks.shift { cpu.v[x] } else { cpu.v[y] };
+
+        cpu.v[x] = value >> 1;
+        cpu.v[0xF] = value & 0x1;
     }
 
     /// 8xy7 - SUBN Vx, Vy
@@ -253,9 +338,17 @@ impl CpuCore {
     pub(super) fn op_8xye(&mut self, cpu: &mut Cpu, opcode: u16) {
         let (x, y) = Self::regs_xy(opcode);
 
-        cpu.v[x] = cpu.v[x] << 1;
-        cpu.v[y] = cpu.v[x];
-        cpu.v[0xF] = (cpu.v[x] & 0x8) >> 3;
+        if self.quirks.legacy_shift {
+            cpu.v[x] <<= 1;
+            cpu.v[y] = cpu.v[x];
+            cpu.v[0xF] = (cpu.v[x] & 0x8) >> 3;
+            return;
+        }
+
+        let value = if self.quirks.shift { cpu.v[x] } else { cpu.v[y] };
+
+        cpu.v[x] = value << 1;
+        cpu.v[0xF] = value >> 7;
     }
 
     // Helper //////////////////////////////////////////////////////////////////
@@ -273,15 +366,17 @@ impl CpuCore {
     pub(super) fn op_annn(&mut self, cpu: &mut Cpu, opcode: u16) {
         let nnn = opcode & 0x0fff;
 
-        cpu.i = nnn;
+        cpu.i = u32::from(nnn);
     }
     /// Bnnn - JP V0, addr
     /// Jump to location nnn + V0.
     /// The program counter is set to nnn plus the value of V0.
     pub(super) fn op_bnnn(&mut self, cpu: &mut Cpu, opcode: u16) {
         let nnn = opcode & 0x0fff;
+        // With the jump quirk the high nibble of nnn also picks the register: Bxnn.
+        let x = if self.quirks.jump { usize::from(nnn >> 8) } else { 0 };
 
-        cpu.pc = (cpu.v[0] as u16) + nnn;
+        cpu.pc = (cpu.v[x] as u16) + nnn;
     }
     /// Cxkk - RND Vx, byte
     /// Set Vx = random byte AND kk.
@@ -312,23 +407,32 @@ impl CpuCore {
         let x = ((opcode & 0x0F00) >> 8) as usize;
         let y = ((opcode & 0x00F0) >> 4) as usize;
         let n = (opcode & 0x000F) as usize;
-        let vx = cpu.v[x] as usize;
-        let vy = cpu.v[y] as usize;
+        // The sprite starts on screen, what goes past the edges is clipped or wrapped.
+        let w = cpu.vram.width();
+        let vx = cpu.v[x] as usize % w;
+        let h = cpu.height();
+        let vy = cpu.v[y] as usize % h;
         let base_mem = cpu.i as usize;
+        // Dxy0 draws a 16x16 sprite on SUPER-CHIP, two bytes a row.
+        let schip = matches!(self.platform, Platform::Schip | Platform::Xochip | Platform::Megachip);
+        let (rows, bytes) = if n == 0 && schip { (16, 2) } else { (n, 1) };
 
         cpu.v[0xf] = 0;
 
-        // TODO: handle the wrap to the opposite side of the screen
-        for j in 0..n {
-            let vram_ptr = vx + (vy + j) * W;
-            
-            for i in 0..8 {
-                let mem_bit = (cpu.mem[base_mem + j] >> (7 - i)) & 1 == 1;
-                let vram_bit = cpu.vram[vram_ptr + i] != 0x00;
+        for j in 0..rows {
+            let row = vy + j;
+            if row >= h && !self.quirks.wrap {
+                break;
+            }
 
-                cpu.vram[vram_ptr + i] = if mem_bit ^ vram_bit { 0xFF } else { 0x00 };
+            for b in 0..bytes {
+                let col = vx + 8 * b;
+                if col >= w && !self.quirks.wrap {
+                    break;
+                }
+                let sprite = cpu.mem[(base_mem + j * bytes + b) % MEMORY_SIZE];
 
-                if mem_bit & vram_bit {
+                if cpu.vram.xor_byte(0, col % w, row % h, sprite, self.quirks.wrap) {
                     cpu.v[0xf] = 1;
                 }
             }
@@ -355,6 +459,7 @@ impl CpuCore {
         // XOR 0 0 = 0
 
         cpu.draw_flag = true;
+        self.wait_vblank = self.quirks.vblank;
     }
 
     /// Ex9E - SKP Vx
@@ -391,11 +496,16 @@ impl CpuCore {
     /// Wait for a key press, store the value of the key in Vx.
     /// All execution stops until a key is pressed, then the value of that key is stored in Vx.
     pub(super) fn op_fx0a(&mut self, cpu: &mut Cpu, opcode: u16) {
-        let _x = usize::from((opcode >> 8) & 0xF);
-
-        cpu.wait_for_key = true;
+        let x = usize::from((opcode >> 8) & 0xF);
 
-        // TODO - waiting for the key to press
+        // While no key is down the PC is not advanced, so the instruction runs again.
+        match cpu.keypad.iter().position(|&pressed| pressed) {
+            Some(key) => {
+                cpu.v[x] = key as u8;
+                cpu.wait_for_key = false;
+            }
+            None => cpu.wait_for_key = true,
+        }
     }
 
     ///Fx15 - LD DT, Vx
@@ -422,7 +532,7 @@ impl CpuCore {
     pub(super) fn op_fx1e(&mut self, cpu: &mut Cpu, opcode: u16) {
         let x = usize::from((opcode >> 8) & 0xF);
 
-        cpu.i += cpu.v[x] as u16;
+        cpu.i += u32::from(cpu.v[x]);
     }
 
     /// Fx29 - LD F, Vx
@@ -432,7 +542,15 @@ impl CpuCore {
     pub(super) fn op_fx29(&mut self, cpu: &mut Cpu, opcode: u16) {
         let x = usize::from((opcode >> 8) & 0xF);
 
-        cpu.i = FONT_ADDR + FONT_SIZE * u16::from(cpu.v[x]);
+        cpu.i = u32::from(FONT_ADDR + FONT_SIZE * u16::from(cpu.v[x]));
+    }
+
+    /// Fx30 - LD HF, Vx - SUPER-CHIP
+    /// Set I = location of the big sprite for the digit Vx, 0 to 9.
+    pub(super) fn op_fx30(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+
+        cpu.i = u32::from(BIG_FONT_ADDR + BIG_FONT_SIZE * u16::from(cpu.v[x] % 10));
     }
 
     /// Fx33 - LD B, Vx
@@ -449,9 +567,11 @@ impl CpuCore {
         let d = bcd.chars().nth(2).unwrap().to_digit(10).unwrap() as u8;
 
 
-        cpu.mem[usize::from(cpu.i)] = b;
-        cpu.mem[usize::from(cpu.i + 1)] = c;
-        cpu.mem[usize::from(cpu.i + 2)] = d;
+        let i = cpu.i as usize;
+        cpu.mem[i] = b;
+        cpu.mem[i + 1] = c;
+        cpu.mem[i + 2] = d;
+        cpu.invalidate(i..i + 3);
     }
 
     /// Fx55 - LD [I], Vx
@@ -464,6 +584,8 @@ impl CpuCore {
         for idx in 0..(x + 1) {
             cpu.mem[cpu.i as usize + idx] = cpu.v[idx];
         }
+        cpu.invalidate(cpu.i as usize..cpu.i as usize + x + 1);
+        self.advance_i(cpu, x);
     }
     /// Fx65 - LD Vx, [I]
     /// Read registers V0 through Vx from memory starting at location I.
@@ -474,6 +596,208 @@ impl CpuCore {
         for idx in 0..(x + 1) {
             cpu.v[idx] = cpu.mem[cpu.i as usize + idx];
         }
+        self.advance_i(cpu, x);
+    }
+
+    /// Fx75 - LD R, Vx - SUPER-CHIP
+    /// Store registers V0 through Vx in the flag registers.
+    pub(super) fn op_fx75(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+
+        cpu.flags[..=x].copy_from_slice(&cpu.v[..=x]);
+    }
+
+    /// Fx85 - LD Vx, R - SUPER-CHIP
+    /// Read registers V0 through Vx from the flag registers.
+    pub(super) fn op_fx85(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+
+        cpu.v[..=x].copy_from_slice(&cpu.flags[..=x]);
+    }
+
+    /// Moves I past the registers stored or loaded by Fx55 / Fx65, as the quirks say.
+    fn advance_i(&self, cpu: &mut Cpu, x: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+
+        let step = if self.quirks.memory_increment_by_x { x } else { x + 1 };
+        cpu.i = cpu.i.wrapping_add(step as u32);
+    }
+
+    /// 02A0 - CHIP-8X
+    /// Cycle the background colour: blue, black, green, red.
+    pub(super) fn op_02a0(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        cpu.colours.cycle_background();
+        self.send(Status::Colours(Some(Box::new(cpu.colours))));
+    }
+
+    /// 5xy1 - CHIP-8X
+    /// Set Vx = Vx + Vy, adding the nibbles apart, each modulo 8.
+    /// Used to move the areas BxyN colours.
+    pub(super) fn op_5xy1(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+        let y = usize::from((opcode >> 4) & 0xF);
+        let (vx, vy) = (cpu.v[x], cpu.v[y]);
+
+        cpu.v[x] = ((vx & 0x70) + (vy & 0x70)) & 0x70 | ((vx & 0x07) + (vy & 0x07)) & 0x07;
+    }
+
+    /// BxyN - CHIP-8X
+    /// Set the foreground colour to Vy. With N = 0, of the zones of 8x4 pixels Vx and Vx+1
+    /// give, see ColourBoard::set_zones. Otherwise of N rows of the 8 pixel wide zone of the
+    /// pixel at (Vx, Vx+1).
+    pub(super) fn op_bxyn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+        let y = usize::from((opcode >> 4) & 0xF);
+        let n = (opcode & 0xF) as u8;
+        let (vx, vx1, vy) = (cpu.v[x], cpu.v[(x + 1) % 16], cpu.v[y]);
+
+        if n == 0 {
+            cpu.colours.set_zones(vx, vx1, vy);
+        } else {
+            cpu.colours.set_rows(vx, vx1, n, vy);
+        }
+        self.send(Status::Colours(Some(Box::new(cpu.colours))));
+    }
+
+    /// ExF2 - CHIP-8X
+    /// Skip next instruction if the key Vx of the second keypad is pressed.
+    pub(super) fn op_exf2(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+        if cpu.keypad2[usize::from(cpu.v[x] & 0xF)] {
+            cpu.pc += 2;
+        }
+    }
+
+    /// ExF5 - CHIP-8X
+    /// Skip next instruction if the key Vx of the second keypad is not pressed.
+    pub(super) fn op_exf5(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+        if !cpu.keypad2[usize::from(cpu.v[x] & 0xF)] {
+            cpu.pc += 2;
+        }
+    }
+
+    /// FxF8 - CHIP-8X
+    /// Set the pitch of the sound board to Vx, the tone still sounds while the sound timer runs.
+    pub(super) fn op_fxf8(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+
+        cpu.tone = Some(cpu.v[x]);
+    }
+
+    /// 0010 / 0011 - MegaChip
+    /// Leave / enter the MegaChip mode and its 256x192 display.
+    pub(super) fn op_001n(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let Some(mega) = cpu.mega.as_deref_mut() else { return };
+
+        mega.enabled = opcode == 0x0011;
+        let enabled = mega.enabled;
+        // The MegaChip instructions replace some of the others in the mode.
+        cpu.invalidate_all();
+        if enabled {
+            self.send(Status::Resolution(megachip::W, megachip::H));
+        } else {
+            self.send(Status::Resolution(cpu.vram.width(), cpu.height()));
+            self.send(Status::TrueColour(None));
+            cpu.draw_flag = true;
+        }
+    }
+
+    /// 00E0 - MegaChip
+    /// Show the frame drawn since the last 00E0, then clear it.
+    pub(super) fn op_00e0_mega(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        let Some(mega) = cpu.mega.as_deref_mut() else { return };
+
+        self.send(Status::TrueColour(Some(mega.clear().into_boxed_slice())));
+    }
+
+    /// 01nn nnnn - MegaChip
+    /// Set I = nnnnnn, the low 16 bits are the next word.
+    pub(super) fn op_01nn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let pc = usize::from(cpu.pc);
+        let low = u16::from_be_bytes([cpu.mem[pc + 2], cpu.mem[pc + 3]]);
+
+        cpu.i = u32::from(opcode & 0xFF) << 16 | u32::from(low);
+        cpu.pc += 2;
+    }
+
+    /// 02nn - MegaChip
+    /// Load nn ARGB colours from I into the palette, from entry 1.
+    pub(super) fn op_02nn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.load_palette(&cpu.mem, cpu.i as usize, opcode as u8);
+        }
+    }
+
+    /// 03nn - MegaChip
+    /// Set the sprite width to nn pixels.
+    pub(super) fn op_03nn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.set_sprite_width(opcode as u8);
+        }
+    }
+
+    /// 04nn - MegaChip
+    /// Set the sprite height to nn pixels.
+    pub(super) fn op_04nn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.set_sprite_height(opcode as u8);
+        }
+    }
+
+    /// 05nn - MegaChip
+    /// Set the alpha the sprites are drawn with, FF is opaque.
+    pub(super) fn op_05nn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.set_alpha(opcode as u8);
+        }
+    }
+
+    /// 060n - MegaChip
+    /// Play the digitised sound at I, over and over when n is 0.
+    pub(super) fn op_060n(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.play(&cpu.mem, cpu.i as usize, (opcode & 0xF) as u8);
+        }
+    }
+
+    /// 0700 - MegaChip
+    /// Stop the digitised sound.
+    pub(super) fn op_0700(&mut self, cpu: &mut Cpu, _opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.stop();
+        }
+    }
+
+    /// 080n - MegaChip
+    /// Set the blend mode: normal, 25%, 50%, 75%, additive or multiply.
+    pub(super) fn op_080n(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.set_blend((opcode & 0xF) as u8);
+        }
+    }
+
+    /// 09nn - MegaChip
+    /// Set the collision colour, drawing over a pixel of palette entry nn sets VF.
+    pub(super) fn op_09nn(&mut self, cpu: &mut Cpu, opcode: u16) {
+        if let Some(mega) = cpu.mega.as_deref_mut() {
+            mega.set_collision(opcode as u8);
+        }
+    }
+
+    /// Dxyn - MegaChip
+    /// Draw the sprite at I at (Vx, Vy), of the size 03nn and 04nn set, set VF = collision.
+    /// The font sprites are n rows high. See MegaChip::draw.
+    pub(super) fn op_dxyn_mega(&mut self, cpu: &mut Cpu, opcode: u16) {
+        let x = usize::from((opcode >> 8) & 0xF);
+        let y = usize::from((opcode >> 4) & 0xF);
+        let n = (opcode & 0xF) as u8;
+        let Some(mega) = cpu.mega.as_deref_mut() else { return };
+
+        let collision = mega.draw(&cpu.mem, cpu.i as usize, cpu.v[x], cpu.v[y], n);
+        cpu.v[0xF] = u8::from(collision);
     }
 }
 
@@ -491,6 +815,149 @@ mod tests {
         assert_eq!(cpu.pc, 0x0234);
         assert_eq!(cpu.sp, 0);
     }
+
+    #[test]
+    fn decode_op_test_0nnn_machine_code() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(Some(tx));
+        cpu_core.platform = Platform::Chip8;
+
+        cpu.mem[0x234] = 0xD4; // SEP 4
+        cpu_core.decode_opcode(&mut cpu, 0x0234);
+        assert_eq!(cpu.pc, 0x0200);
+        assert!(!cpu.wait_for_key);
+
+        // IDL, the call is skipped.
+        cpu_core.decode_opcode(&mut cpu, 0x0000);
+        assert!(matches!(rx.try_recv(), Ok(Status::Error(_))));
+        assert_eq!(cpu.pc, 0x0200);
+        assert!(!cpu.panic);
+    }
+    #[test]
+    fn decode_op_test_hires() {
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(None);
+        cpu.set_hires();
+
+        cpu.v[1] = 40;
+        cpu.mem[0x300] = 0x80;
+        cpu.i = 0x300;
+        cpu_core.decode_opcode(&mut cpu, 0xD011);
+        assert_eq!(cpu.vram.get(0, 40), 1);
+
+        cpu_core.decode_opcode(&mut cpu, 0x0230);
+        assert_eq!(cpu.vram.get(0, 40), 0);
+    }
+    #[test]
+    fn decode_op_test_chip8x() {
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(None);
+        cpu_core.platform = Platform::Chip8x;
+
+        cpu.v[1] = 0x37;
+        cpu.v[2] = 0x15;
+        cpu_core.decode_opcode(&mut cpu, 0x5121);
+        assert_eq!(cpu.v[1], 0x44);
+
+        cpu.keypad2[5] = true;
+        cpu_core.decode_opcode(&mut cpu, 0xE2F2);
+        assert_eq!(cpu.pc, 0x202);
+        cpu_core.decode_opcode(&mut cpu, 0xE2F5);
+        assert_eq!(cpu.pc, 0x202);
+
+        cpu_core.decode_opcode(&mut cpu, 0xF2F8);
+        assert_eq!(cpu.tone, Some(0x15));
+
+        // Bnnn jumps on the other platforms.
+        cpu_core.decode_opcode(&mut cpu, 0xB241);
+        assert_eq!(cpu.pc, 0x202);
+        assert_ne!(cpu.colours, Cpu::new().colours);
+    }
+    #[test]
+    fn decode_op_test_megachip() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(Some(tx));
+        cpu.mem.resize(megachip::MEMORY_SIZE, 0);
+        cpu.mega = Some(Box::default());
+
+        // The MegaChip instructions are SUPER-CHIP ones or machine code calls until 0011.
+        cpu_core.decode_opcode(&mut cpu, 0x0011);
+        assert_eq!(rx.try_recv(), Ok(Status::Resolution(megachip::W, megachip::H)));
+
+        cpu.mem[0x202..0x204].copy_from_slice(&[0x00, 0x10]);
+        cpu_core.decode_opcode(&mut cpu, 0x0112);
+        assert_eq!(cpu.i, 0x12_0010);
+        assert_eq!(cpu.pc, 0x202);
+
+        cpu.mem[0x12_0010] = 0x01;
+        cpu.v[1] = 3;
+        cpu_core.decode_opcode(&mut cpu, 0x0301);
+        cpu_core.decode_opcode(&mut cpu, 0x0401);
+        cpu_core.decode_opcode(&mut cpu, 0x0901);
+        cpu_core.decode_opcode(&mut cpu, 0xD110);
+        assert_eq!(cpu.v[0xF], 0);
+        cpu_core.decode_opcode(&mut cpu, 0xD110);
+        assert_eq!(cpu.v[0xF], 1);
+        assert!(!cpu.draw_flag);
+
+        cpu_core.decode_opcode(&mut cpu, 0x00E0);
+        match rx.try_recv() {
+            Ok(Status::TrueColour(Some(rgba))) => assert_eq!(rgba[(3 * megachip::W + 3) * 4..][..4], [0xFF; 4]),
+            status => panic!("{status:?}"),
+        }
+
+        cpu_core.decode_opcode(&mut cpu, 0x0010);
+        assert_eq!(rx.try_recv(), Ok(Status::Resolution(W, 32)));
+        assert_eq!(rx.try_recv(), Ok(Status::TrueColour(None)));
+    }
+    #[test]
+    fn decode_op_test_schip() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(Some(tx));
+        cpu_core.platform = Platform::Schip;
+
+        cpu_core.decode_opcode(&mut cpu, 0x00FF);
+        assert_eq!(rx.try_recv(), Ok(Status::Resolution(SCHIP_W, SCHIP_H)));
+        assert_eq!((cpu.vram.width(), cpu.height()), (SCHIP_W, SCHIP_H));
+        assert!(!cpu.hires());
+
+        // A 16x16 sprite, clipped at the bottom right corner.
+        cpu.i = 0x400;
+        cpu.mem[0x400..0x420].fill(0xFF);
+        cpu.v[1] = 120;
+        cpu.v[2] = 60;
+        cpu_core.decode_opcode(&mut cpu, 0xD120);
+        assert_eq!(cpu.vram.pixels().filter(|&p| p != 0).count(), 8 * 4);
+        assert_eq!(cpu.vram.get(127, 63), 1);
+        assert_eq!(cpu.v[0xF], 0);
+
+        cpu.v[3] = 7;
+        cpu_core.decode_opcode(&mut cpu, 0xF330);
+        assert_eq!(cpu.i, u32::from(BIG_FONT_ADDR) + 70);
+
+        cpu.v[..3].copy_from_slice(&[1, 2, 3]);
+        cpu_core.decode_opcode(&mut cpu, 0xF275);
+        cpu.v = [0; 16];
+        cpu_core.decode_opcode(&mut cpu, 0xF285);
+        assert_eq!(cpu.v[..4], [1, 2, 3, 0]);
+
+        cpu_core.decode_opcode(&mut cpu, 0x00C2);
+        cpu_core.decode_opcode(&mut cpu, 0x00FC);
+        assert_eq!(cpu.vram.pixels().filter(|&p| p != 0).count(), 8 * 2);
+        assert_eq!((cpu.vram.get(116, 62), cpu.vram.get(124, 63)), (1, 0));
+        cpu_core.decode_opcode(&mut cpu, 0x00FB);
+        assert_eq!((cpu.vram.get(116, 62), cpu.vram.get(127, 63)), (0, 1));
+
+        cpu_core.decode_opcode(&mut cpu, 0x00FE);
+        assert_eq!(rx.try_recv(), Ok(Status::Resolution(W, H)));
+        assert_eq!(cpu.vram.pixels().filter(|&p| p != 0).count(), 0);
+
+        cpu_core.decode_opcode(&mut cpu, 0x00FD);
+        assert!(!cpu.running);
+    }
     #[test]
     fn decode_op_test_00e0() {
         let mut cpu = Cpu::new();
@@ -501,7 +968,7 @@ mod tests {
         // cpu.vram.iter().for_each(|item| {
         //     assert_eq!(*item, false);
         // });
-        assert_eq!(cpu.draw_flag, true);
+        assert!(cpu.draw_flag);
     }
     #[test]
     fn decode_op_test_00ee() {
@@ -763,6 +1230,27 @@ mod tests {
         assert_eq!(cpu.v[0xF], 0);
     }
 
+    #[test]
+    fn decode_op_test_8xy6_quirks() {
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(None);
+        cpu_core.quirks = Platform::Chip8.quirks();
+
+        cpu.v[5] = 0xEF;
+        cpu.v[6] = 0x34;
+        cpu_core.decode_opcode(&mut cpu, 0x8566);
+        assert_eq!(cpu.v[5], 0x1A);
+        assert_eq!(cpu.v[6], 0x34);
+        assert_eq!(cpu.v[0xF], 0);
+
+        cpu_core.quirks.shift = true;
+        cpu.v[5] = 0xEF;
+        cpu_core.decode_opcode(&mut cpu, 0x8566);
+        assert_eq!(cpu.v[5], 0x77);
+        assert_eq!(cpu.v[6], 0x34);
+        assert_eq!(cpu.v[0xF], 1);
+    }
+
     #[test]
     fn decode_op_test_8xy7_with_carry() {
         let mut cpu = Cpu::new();
@@ -811,6 +1299,26 @@ mod tests {
         assert_eq!(cpu.v[6], 0xEE);
         assert_eq!(cpu.v[0xF], 1);
     }
+
+    #[test]
+    fn decode_op_test_8xye_quirks() {
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(None);
+        cpu_core.quirks = Platform::Chip8.quirks();
+
+        cpu.v[5] = 0x77;
+        cpu.v[6] = 0xDF;
+        cpu_core.decode_opcode(&mut cpu, 0x856e);
+        assert_eq!(cpu.v[5], 0xBE);
+        assert_eq!(cpu.v[6], 0xDF);
+        assert_eq!(cpu.v[0xF], 1);
+
+        cpu_core.quirks.shift = true;
+        cpu.v[5] = 0x77;
+        cpu_core.decode_opcode(&mut cpu, 0x856e);
+        assert_eq!(cpu.v[5], 0xEE);
+        assert_eq!(cpu.v[0xF], 0);
+    }
     #[test]
     fn decode_op_test_annn() {
         let mut cpu = Cpu::new();
@@ -829,6 +1337,11 @@ mod tests {
         cpu.v[0] = 0x10;
         cpu_core.decode_opcode(&mut cpu, 0xB500);
         assert_eq!(cpu.pc, 0x510);
+
+        cpu_core.quirks.jump = true;
+        cpu.v[5] = 0x20;
+        cpu_core.decode_opcode(&mut cpu, 0xB500);
+        assert_eq!(cpu.pc, 0x520);
     }
     #[test]
     fn decode_op_test_cxkk_and_0() {
@@ -882,6 +1395,27 @@ mod tests {
         // assert!(cpu.vram[W * 0x12 + 0x20 + 5]);
         // assert!(cpu.vram[W * 0x13 + 0x20 + 4]);
     }
+
+    #[test]
+    fn decode_op_test_dxyn_clips_or_wraps() {
+        let mut cpu = Cpu::new();
+        let mut cpu_core = CpuCore::new(None);
+
+        cpu.i = 0x400;
+        cpu.mem[0x400] = 0xFF;
+        cpu.mem[0x401] = 0xFF;
+        cpu.v[2] = 60 + W as u8;
+        cpu.v[3] = 31;
+        cpu_core.decode_opcode(&mut cpu, 0xD232);
+        assert_eq!(cpu.vram.pixels().filter(|&p| p != 0).count(), 4);
+        assert!((60..W).all(|x| cpu.vram.get(x, 31) == 1));
+
+        cpu.vram.clear();
+        cpu_core.quirks.wrap = true;
+        cpu_core.decode_opcode(&mut cpu, 0xD232);
+        assert_eq!(cpu.vram.pixels().filter(|&p| p != 0).count(), 16);
+        assert!((0..4).all(|x| cpu.vram.get(x, 0) == 1));
+    }
     /// Ex9E - SKP Vx
     /// Skip next instruction if key with the value of Vx is pressed.
     /// Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down
@@ -914,7 +1448,16 @@ mod tests {
     /// All execution stops until a key is pressed, then the value of that key is stored in Vx.
     #[test]
     fn decode_op_test_fx0a() {
-        println!("decode_op_test_fx0a to be implemented");
+        let mut cpu = Cpu::new();
+        let mut core = CpuCore::new(None);
+
+        core.decode_opcode(&mut cpu, 0xF30A);
+        assert!(cpu.wait_for_key);
+
+        cpu.keypad[0xB] = true;
+        core.decode_opcode(&mut cpu, 0xF30A);
+        assert!(!cpu.wait_for_key);
+        assert_eq!(cpu.v[0x3], 0xB);
     }
     ///Fx15 - LD DT, Vx
     /// Set delay timer = Vx.
@@ -965,7 +1508,7 @@ mod tests {
         cpu.i = 0x888;
         cpu.v[0x1] = 0x3;
         core.decode_opcode(&mut cpu, 0xF129);
-        assert_eq!(cpu.i, FONT_ADDR + 15);
+        assert_eq!(cpu.i, u32::from(FONT_ADDR) + 15);
     }
     /// Fx33 - LD B, Vx
     /// Store BCD representation of Vx in memory locations I, I+1, and I+2.
@@ -1008,6 +1551,19 @@ mod tests {
             assert_eq!(cpu.v[idx], cpu.mem[0x502 + idx]);
         }
         assert_eq!(cpu.v[7], 0x0);
+        assert_eq!(cpu.i, 0x502);
+
+        core.quirks = Platform::Modern.quirks();
+        core.decode_opcode(&mut cpu, 0xF655);
+        assert_eq!(cpu.i, 0x509);
+
+        core.quirks.memory_increment_by_x = true;
+        core.decode_opcode(&mut cpu, 0xF655);
+        assert_eq!(cpu.i, 0x50F);
+
+        core.quirks.memory_leave_i_unchanged = true;
+        core.decode_opcode(&mut cpu, 0xF655);
+        assert_eq!(cpu.i, 0x50F);
     }
 
     /// Fx65 - LD Vx, [I]
@@ -0,0 +1,107 @@
+//! Instruction mnemonics, in the notation of Cowan's CHIP-8 technical reference.
+
+use crate::instruction::Instruction;
+
+/// The mnemonic of `instruction`, decoded from `opcode`. `DW` for data that is no instruction.
+pub fn mnemonic(instruction: Instruction, opcode: u16) -> String {
+    use Instruction::*;
+
+    let nnn = opcode & 0x0FFF;
+    let n = opcode & 0x000F;
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let kk = opcode & 0x00FF;
+
+    match instruction {
+        Sys | MachineRet => format!("SYS #{nnn:03X}"),
+        Cls | MegaCls => "CLS".into(),
+        Ret => "RET".into(),
+        Jp => format!("JP #{nnn:03X}"),
+        Call => format!("CALL #{nnn:03X}"),
+        SeByte => format!("SE V{x:X}, #{kk:02X}"),
+        SneByte => format!("SNE V{x:X}, #{kk:02X}"),
+        SeReg => format!("SE V{x:X}, V{y:X}"),
+        LdByte => format!("LD V{x:X}, #{kk:02X}"),
+        AddByte => format!("ADD V{x:X}, #{kk:02X}"),
+        LdReg => format!("LD V{x:X}, V{y:X}"),
+        Or => format!("OR V{x:X}, V{y:X}"),
+        And => format!("AND V{x:X}, V{y:X}"),
+        Xor => format!("XOR V{x:X}, V{y:X}"),
+        AddReg => format!("ADD V{x:X}, V{y:X}"),
+        Sub => format!("SUB V{x:X}, V{y:X}"),
+        Shr => format!("SHR V{x:X}, V{y:X}"),
+        Subn => format!("SUBN V{x:X}, V{y:X}"),
+        Shl => format!("SHL V{x:X}, V{y:X}"),
+        SneReg => format!("SNE V{x:X}, V{y:X}"),
+        LdI => format!("LD I, #{nnn:03X}"),
+        JpV0 => format!("JP V0, #{nnn:03X}"),
+        Rnd => format!("RND V{x:X}, #{kk:02X}"),
+        Drw | MegaDrw => format!("DRW V{x:X}, V{y:X}, {n}"),
+        Skp => format!("SKP V{x:X}"),
+        Sknp => format!("SKNP V{x:X}"),
+        LdVxDt => format!("LD V{x:X}, DT"),
+        LdVxK => format!("LD V{x:X}, K"),
+        LdDtVx => format!("LD DT, V{x:X}"),
+        LdStVx => format!("LD ST, V{x:X}"),
+        AddIVx => format!("ADD I, V{x:X}"),
+        LdF => format!("LD F, V{x:X}"),
+        LdB => format!("LD B, V{x:X}"),
+        Store => format!("LD [I], V{x:X}"),
+        Load => format!("LD V{x:X}, [I]"),
+        Background => "BGC".into(),
+        AddNibbles => format!("ADDN V{x:X}, V{y:X}"),
+        Colour => format!("COL V{x:X}, V{y:X}, {n}"),
+        Skp2 => format!("SKP2 V{x:X}"),
+        Sknp2 => format!("SKNP2 V{x:X}"),
+        Pitch => format!("TONE V{x:X}"),
+        MegaMode if opcode & 1 == 1 => "MEGAON".into(),
+        MegaMode => "MEGAOFF".into(),
+        LdILong => format!("LDHI #{kk:02X}"),
+        Palette => format!("LDPAL #{kk:02X}"),
+        SpriteWidth => format!("SPRW #{kk:02X}"),
+        SpriteHeight => format!("SPRH #{kk:02X}"),
+        Alpha => format!("ALPHA #{kk:02X}"),
+        PlaySample => format!("DIGISND {n}"),
+        StopSample => "STOPSND".into(),
+        Blend => format!("BMODE {n}"),
+        Collision => format!("CCOL #{kk:02X}"),
+        ScrollDown => format!("SCD {n}"),
+        ScrollRight => "SCR".into(),
+        ScrollLeft => "SCL".into(),
+        Exit => "EXIT".into(),
+        Low => "LOW".into(),
+        High => "HIGH".into(),
+        LdHf => format!("LD HF, V{x:X}"),
+        SaveFlags => format!("LD R, V{x:X}"),
+        LoadFlags => format!("LD V{x:X}, R"),
+        ScrollUp => format!("SCU {n}"),
+        SaveRange => format!("SAVE V{x:X}-V{y:X}"),
+        LoadRange => format!("LOAD V{x:X}-V{y:X}"),
+        LdIWord => "LD I, long".into(),
+        Plane => format!("PLANE {x}"),
+        Audio => "AUDIO".into(),
+        AudioPitch => format!("PITCH V{x:X}"),
+        Unknown => format!("DW #{opcode:04X}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Decoder;
+
+    #[test]
+    fn mnemonics() {
+        let decoder = Decoder::new();
+        let mnemonic = |opcode| mnemonic(decoder.decode(opcode), opcode);
+
+        assert_eq!(mnemonic(0x00E0), "CLS");
+        assert_eq!(mnemonic(0x2ABC), "CALL #ABC");
+        assert_eq!(mnemonic(0x8A1E), "SHL VA, V1");
+        assert_eq!(mnemonic(0xD125), "DRW V1, V2, 5");
+        assert_eq!(mnemonic(0xF365), "LD V3, [I]");
+        assert_eq!(mnemonic(0x00FB), "SCR");
+        assert_eq!(mnemonic(0xF000), "LD I, long");
+        assert_eq!(mnemonic(0xFFFF), "DW #FFFF");
+    }
+}
@@ -0,0 +1,722 @@
+use crate::audio::Beeper;
+use crate::chip8x;
+use crate::clock::{Clock, Sync};
+use crate::control::{Command, Status};
+use crate::cpu::{sha1, Cpu, HIRES_SIGNATURE, HIRES_START};
+use crate::cpu_core::{CpuCore, START_ADDRESS};
+use crate::cpu_debugger::CpuDebugger;
+use crate::megachip::{self, Sound};
+use crate::profiler::Profiler;
+use crate::quirks::{Platform, Quirks};
+use crate::rom_db::RomDb;
+use crate::rom_file::{self, RomFile};
+use crate::vip::Vip;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::{Duration, Instant, SystemTime};
+
+const WATCH_POLL: Duration = Duration::from_millis(500);
+/// With vsync or audio pacing the clock takes over when no tick came for this long, e.g.
+/// while the window is minimized.
+const TICK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Instructions per frame.
+pub const DEFAULT_SPEED: u32 = 10;
+/// The steps of the speed up / speed down hotkeys, in instructions per frame.
+const SPEEDS: [u32; 14] = [1, 2, 3, 5, 7, 10, 15, 20, 30, 50, 100, 200, 500, 1000];
+
+/// The next speed step above `ipf`.
+pub fn faster(ipf: u32) -> u32 {
+    SPEEDS.iter().copied().find(|&s| s > ipf).unwrap_or(ipf)
+}
+
+/// The next speed step below `ipf`.
+pub fn slower(ipf: u32) -> u32 {
+    SPEEDS.iter().rev().copied().find(|&s| s < ipf).unwrap_or(ipf)
+}
+
+/// The CPU thread: runs the ROM and serves the frontend commands.
+pub struct Emulator {
+    cpu: Cpu,
+    core: CpuCore,
+    clock: Clock,
+    sync: Sync,
+    beeper: Option<Beeper>,
+    debugger: Option<CpuDebugger>,
+    /// Runs the ROMs on the emulated COSMAC VIP instead of the CPU core.
+    vip: Option<Vip>,
+    rom: Option<PathBuf>,
+    rom_file: Option<RomFile>,
+    rom_db: RomDb,
+    /// Settings given on the command line, they win over the ROM database.
+    speed_override: Option<u32>,
+    platform_override: Option<Platform>,
+    /// Set when a new ROM is loaded, the database settings are applied once it is read.
+    new_rom: bool,
+    status: Sender<Status>,
+    paused: bool,
+    speed: u32,
+    turbo: bool,
+    watch: bool,
+    /// Runs every ROM on the 64x64 display, not only the ones starting with the hi-res jump.
+    hires: bool,
+    rom_modified: Option<SystemTime>,
+    last_watch: Instant,
+}
+
+impl Emulator {
+    pub fn new(status: Sender<Status>) -> Self {
+        Self {
+            cpu: Cpu::new(),
+            core: CpuCore::new(Some(status.clone())),
+            clock: Clock::new(),
+            sync: Sync::Timer,
+            beeper: None,
+            debugger: None,
+            vip: None,
+            rom: None,
+            rom_file: None,
+            rom_db: RomDb::bundled(),
+            speed_override: None,
+            platform_override: None,
+            new_rom: false,
+            status,
+            paused: false,
+            speed: DEFAULT_SPEED,
+            turbo: false,
+            watch: false,
+            hires: false,
+            rom_modified: None,
+            last_watch: Instant::now(),
+        }
+    }
+
+    /// Instructions run on every 60 Hz frame.
+    pub fn set_speed(&mut self, ipf: u32) {
+        self.speed = ipf.max(1);
+    }
+
+    /// Where the ROM settings are looked up on every ROM load.
+    pub fn set_rom_db(&mut self, rom_db: RomDb) {
+        self.rom_db = rom_db;
+    }
+
+    /// Speed and platform that win over the ROM database, `None` to take them from it.
+    pub fn set_overrides(&mut self, speed: Option<u32>, platform: Option<Platform>) {
+        self.speed_override = speed;
+        self.platform_override = platform;
+        self.core.platform = platform.unwrap_or_default();
+        self.core.quirks = platform.map_or(Quirks::original(), Platform::quirks);
+        if let Some(ipf) = speed {
+            self.set_speed(ipf);
+        }
+    }
+
+    /// What the frames are paced against. Vsync and Audio need the frontend or the beeper
+    /// to send the ticks.
+    pub fn set_sync(&mut self, sync: Sync) {
+        self.sync = sync;
+    }
+
+    /// Sounds the buzzer while the sound timer runs.
+    pub fn set_beeper(&mut self, beeper: Beeper) {
+        self.beeper = Some(beeper);
+    }
+
+    /// Shows the registers and the measured speed in the terminal after every frame.
+    pub fn set_debugger(&mut self, debugger: CpuDebugger) {
+        self.debugger = Some(debugger);
+    }
+
+    /// Runs the ROMs on the original CHIP-8 interpreter of an emulated COSMAC VIP.
+    pub fn set_vip(&mut self, vip: Vip) {
+        self.vip = Some(vip);
+    }
+
+    /// Counts the instructions run, see `take_profiler`.
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.core.profiler = Some(profiler);
+    }
+
+    /// The profile of the ROM run last.
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.core.profiler.take()
+    }
+
+    /// Reloads the ROM whenever the file changes on disk.
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+    }
+
+    /// Shows every ROM on the 64x64 display of the hi-res variant.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+    }
+
+    /// Loads a ROM on a clean machine. When it cannot be read the kernel panic screen is shown.
+    /// `path` can be `-` for stdin or `archive.zip#entry`, see rom_file.
+    pub fn load_rom(&mut self, path: &Path) {
+        self.rom = Some(path.to_path_buf());
+        self.new_rom = true;
+        self.read_rom();
+        self.reset();
+    }
+
+    /// Restarts the current ROM from a clean machine.
+    pub fn reset(&mut self) {
+        self.cpu = Cpu::new();
+        if let Some(beeper) = &self.beeper {
+            beeper.play(Sound::Stop);
+        }
+
+        // Taken for the time of the reset, picking the ROM settings needs the whole emulator.
+        match self.rom_file.take() {
+            None => self.cpu.panic(),
+            Some(file) => {
+                if self.new_rom {
+                    self.apply_rom_settings(&sha1(&file.data), file.tickrate, file.quirks);
+                }
+                self.load(&file.data);
+                self.rom_file = Some(file);
+            }
+        }
+        self.new_rom = false;
+
+        // The hi-res programs start with a jump over the changes they make to the interpreter.
+        let signature = self.cpu.mem[START_ADDRESS..START_ADDRESS + 2] == HIRES_SIGNATURE;
+        if !self.cpu.panic && self.vip.is_none() && (self.hires || signature) {
+            self.cpu.set_hires();
+            if signature {
+                self.cpu.pc = HIRES_START;
+            }
+        }
+
+        self.cpu.vram.take_dirty();
+        self.send(Status::Frame(self.cpu.vram.clone()));
+        self.send(Status::Resolution(self.cpu.vram.width(), self.cpu.height()));
+        self.send(Status::TrueColour(None));
+        let colours = self.core.platform == Platform::Chip8x;
+        self.send(Status::Colours(colours.then(|| Box::new(self.cpu.colours))));
+    }
+
+    /// Loads the ROM where the platform runs it from.
+    fn load(&mut self, data: &[u8]) {
+        if self.core.platform == Platform::Megachip {
+            self.cpu.mem.resize(megachip::MEMORY_SIZE, 0);
+            self.cpu.mega = Some(Box::default());
+        }
+        if matches!(self.core.platform, Platform::Schip | Platform::Xochip | Platform::Megachip) {
+            self.cpu.load_big_font();
+        }
+
+        let loaded = self.cpu.load_rom_at(data, self.core.platform.start_address());
+        let booted = match &mut self.vip {
+            Some(vip) if loaded.is_ok() => vip.boot(data),
+            _ => Ok(()),
+        };
+
+        match loaded.map_err(anyhow::Error::from).and(booted) {
+            Ok(()) => {
+                if let Some(profiler) = &mut self.core.profiler {
+                    profiler.start(data);
+                }
+            }
+            Err(e) => {
+                let rom = self.rom.as_deref().unwrap_or(Path::new("-"));
+                self.send(Status::Error(format!("Cannot load {}: {e}", rom.display())));
+                self.cpu.panic();
+            }
+        }
+    }
+
+    /// Reads the ROM file again, Reset runs what was read last.
+    fn read_rom(&mut self) {
+        self.rom_modified = self.modified();
+        self.rom_file = None;
+
+        if let Some(rom) = &self.rom {
+            match rom_file::read(rom) {
+                Ok(file) => self.rom_file = Some(file),
+                Err(e) => self.send(Status::Error(format!("Cannot load {}: {e}", rom.display()))),
+            }
+        }
+    }
+
+    /// Picks the platform, quirks and speed of a new ROM: the command line first, then the
+    /// settings stored in the ROM file (Octo cartridges), then the ROM database, then the
+    /// defaults.
+    fn apply_rom_settings(&mut self, sha1: &str, tickrate: Option<u32>, quirks: Option<Quirks>) {
+        let entry = self.rom_db.get(sha1);
+        let platform = self
+            .platform_override
+            .or(entry.and_then(|e| e.platform()))
+            .unwrap_or_default();
+        let speed = self
+            .speed_override
+            .or(tickrate)
+            .or(entry.and_then(|e| e.tickrate))
+            .unwrap_or(DEFAULT_SPEED);
+
+        self.core.platform = platform;
+        self.core.quirks = match quirks {
+            Some(quirks) if self.platform_override.is_none() => quirks,
+            _ => match entry {
+                Some(entry) => entry.quirks(platform),
+                None if self.platform_override.is_some() => platform.quirks(),
+                None => Quirks::original(),
+            },
+        };
+
+        if speed != self.speed {
+            self.set_speed(speed);
+            self.send(Status::Speed(self.speed));
+        }
+    }
+
+    /// Runs until a Quit command arrives or the frontend goes away.
+    pub fn run(&mut self, commands: Receiver<Command>) {
+        loop {
+            if self.paused || !self.cpu.running {
+                // Nothing to run, sleep until the frontend asks for something
+                // or it is time to look at the ROM file again.
+                let timeout = if self.watch { WATCH_POLL } else { Duration::MAX };
+                match commands.recv_timeout(timeout) {
+                    Ok(cmd) => {
+                        if !self.handle(cmd) {
+                            return;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => self.watch_rom(),
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                // Do not try to catch up on the time spent idle.
+                self.clock.restart();
+                continue;
+            }
+
+            if self.sync == Sync::Timer || self.turbo {
+                loop {
+                    match commands.try_recv() {
+                        Ok(cmd) => {
+                            if !self.handle(cmd) {
+                                return;
+                            }
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => return,
+                    }
+                }
+
+                if !self.paused && self.cpu.running {
+                    let frames = if self.turbo { 1 } else { self.clock.wait() };
+                    self.run_frames(frames);
+                }
+            } else {
+                // The ticks arrive as commands and run the frames in handle().
+                match commands.recv_timeout(TICK_TIMEOUT) {
+                    Ok(cmd) => {
+                        if !self.handle(cmd) {
+                            return;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        let frames = self.clock.tick(Instant::now());
+                        self.run_frames(frames);
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            if self.watch && self.last_watch.elapsed() >= WATCH_POLL {
+                self.watch_rom();
+            }
+
+            if !self.cpu.running {
+                self.send(Status::Halted);
+            }
+        }
+    }
+
+    /// Runs `frames` frames back to back, then updates the buzzer and the debugger.
+    fn run_frames(&mut self, frames: u32) {
+        for _ in 0..frames {
+            let instructions = self.run_frame();
+            self.clock.record(instructions);
+        }
+
+        if let Some(beeper) = &self.beeper {
+            let tone = match &self.vip {
+                Some(vip) => vip.q(),
+                None => self.cpu.sound_timer > 0,
+            };
+            beeper.set_tone(tone);
+            beeper.set_pitch(self.cpu.tone.map(chip8x::frequency));
+
+            if let Some(sound) = self.cpu.mega.as_mut().and_then(|m| m.sound.take()) {
+                beeper.play(sound);
+            }
+        }
+
+        if let Some(debugger) = &mut self.debugger
+            && let Err(e) = debugger.tick(&mut self.cpu, &self.clock, self.core.profiler.as_ref())
+        {
+            self.send(Status::Error(format!("Debugger: {e}")));
+        }
+    }
+
+    /// Runs one frame and sends the display when it changed. Returns the number of
+    /// instructions run.
+    fn run_frame(&mut self) -> u32 {
+        let ran = if !self.cpu.panic && self.vip.is_some() {
+            self.run_vip_frame()
+        } else {
+            self.run_core_frame()
+        };
+
+        if self.cpu.vram.take_dirty() {
+            self.send(Status::Frame(self.cpu.vram.clone()));
+        }
+        ran
+    }
+
+    /// Runs the instructions of one frame on the CPU core, then counts the timers down.
+    fn run_core_frame(&mut self) -> u32 {
+        let mut ran = 0;
+
+        while ran < self.speed {
+            if !self.cpu.running || self.cpu.panic {
+                return ran;
+            }
+            ran += self.core.run_block(&mut self.cpu, self.speed - ran);
+
+            // The VIP interpreter waits for the next frame before drawing.
+            if self.core.wait_vblank {
+                self.core.wait_vblank = false;
+                break;
+            }
+        }
+
+        self.core.tick_timers(&mut self.cpu);
+        ran
+    }
+
+    /// Runs a frame of the VIP, the keypad is the one of the frontend. Returns the number of
+    /// 1802 instructions run.
+    fn run_vip_frame(&mut self) -> u32 {
+        let Some(vip) = &mut self.vip else { return 0 };
+
+        vip.set_keypad(self.cpu.keypad);
+        let ran = vip.run_frame();
+
+        vip.draw(&mut self.cpu.vram);
+        ran
+    }
+
+    /// Returns false when the thread has to end.
+    fn handle(&mut self, cmd: Command) -> bool {
+        match cmd {
+            Command::Quit => return false,
+            Command::Pause => self.paused = true,
+            Command::Resume => self.paused = false,
+            Command::Reset => self.reset(),
+            Command::LoadRom(path) => self.load_rom(&path),
+            Command::SetSpeed(ipf) => self.set_speed(ipf),
+            Command::Turbo(turbo) => {
+                self.turbo = turbo;
+                self.clock.restart();
+            }
+            Command::Step => {
+                if self.paused && self.cpu.running {
+                    self.run_frames(1);
+                }
+            }
+            Command::Key(key, pressed) => {
+                if let Some(k) = self.cpu.keypad.get_mut(usize::from(key)) {
+                    *k = pressed;
+                }
+            }
+            Command::Key2(key, pressed) => {
+                if let Some(k) = self.cpu.keypad2.get_mut(usize::from(key)) {
+                    *k = pressed;
+                }
+            }
+            Command::Tick(source) => {
+                if source == self.sync && !self.turbo && !self.paused && self.cpu.running {
+                    // The audio device plays exactly 60 ticks per second of its own clock.
+                    let frames = match source {
+                        Sync::Audio => 1,
+                        _ => self.clock.tick(Instant::now()),
+                    };
+                    self.run_frames(frames);
+                }
+            }
+        }
+
+        true
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        let rom = self.rom.as_ref()?;
+        fs::metadata(rom_file::disk_path(rom)).and_then(|m| m.modified()).ok()
+    }
+
+    fn watch_rom(&mut self) {
+        self.last_watch = Instant::now();
+
+        let modified = self.modified();
+        if modified.is_some() && modified != self.rom_modified {
+            // A rebuilt ROM is another ROM to the database.
+            self.new_rom = true;
+            self.read_rom();
+            self.reset();
+        }
+    }
+
+    fn send(&self, status: Status) {
+        // Nobody to tell when the frontend is gone.
+        let _ = self.status.send(status);
+    }
+}
+
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        if let Some(debugger) = &mut self.debugger {
+            let _ = debugger.quit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn load_rom_sends_first_frame() {
+        let (tx, rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+
+        assert!(matches!(rx.try_recv(), Ok(Status::Frame(_))));
+        assert!(!emulator.cpu.panic);
+    }
+
+    #[test]
+    fn load_missing_rom_reports_error_and_panics() {
+        let (tx, rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.load_rom(Path::new("tests/missing.ch8"));
+
+        assert!(matches!(rx.try_recv(), Ok(Status::Error(_))));
+        assert!(matches!(rx.try_recv(), Ok(Status::Frame(_))));
+        assert!(emulator.cpu.panic);
+    }
+
+    #[test]
+    fn reset_reloads_the_rom() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+        let rom = emulator.cpu.mem.clone();
+        emulator.cpu.mem[START_ADDRESS] = 0;
+        emulator.cpu.v[3] = 7;
+
+        assert!(emulator.handle(Command::Reset));
+        assert_eq!(emulator.cpu.mem, rom);
+        assert_eq!(emulator.cpu.v[3], 0);
+    }
+
+    #[test]
+    fn load_rom_applies_the_rom_database() {
+        let (tx, rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.load_rom(Path::new("tests/8-scrolling.ch8"));
+        assert_eq!(emulator.speed, 30);
+        assert_eq!(emulator.core.quirks, Platform::Schip.quirks());
+        assert!(rx.try_iter().any(|s| s == Status::Speed(30)));
+
+        emulator.set_speed(7);
+        emulator.reset();
+        assert_eq!(emulator.speed, 7);
+
+        emulator.set_overrides(Some(12), Some(Platform::Xochip));
+        emulator.load_rom(Path::new("tests/8-scrolling.ch8"));
+        assert_eq!(emulator.speed, 12);
+        assert_eq!(emulator.core.quirks, Platform::Xochip.quirks());
+
+        emulator.set_overrides(None, None);
+        emulator.load_rom(Path::new("tests/test_opcode.ch8"));
+        assert_eq!(emulator.speed, DEFAULT_SPEED);
+        assert_eq!(emulator.core.quirks, Platform::Modern.quirks());
+    }
+
+    #[test]
+    fn chip8x_programs_start_at_0300_in_colour() {
+        let (tx, rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+        emulator.set_overrides(None, Some(Platform::Chip8x));
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+
+        assert_eq!(emulator.cpu.pc, 0x300);
+        assert_eq!(emulator.cpu.mem[0x300..0x302], [0x00, 0xE0]);
+        assert_eq!(emulator.cpu.mem[START_ADDRESS], 0);
+        assert!(rx.try_iter().any(|s| matches!(s, Status::Colours(Some(_)))));
+
+        emulator.handle(Command::Key2(0x3, true));
+        assert!(emulator.cpu.keypad2[0x3]);
+    }
+
+    #[test]
+    fn the_hires_jump_selects_the_64x64_display() {
+        let (tx, rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hires.ch8");
+        fs::write(&path, [0x12, 0x60]).unwrap();
+
+        emulator.load_rom(&path);
+
+        assert!(emulator.cpu.hires());
+        assert_eq!(emulator.cpu.pc, HIRES_START);
+        assert!(rx.try_iter().any(|s| s == Status::Resolution(64, 64)));
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+        assert!(!emulator.cpu.hires());
+        emulator.set_hires(true);
+        emulator.reset();
+        assert!(emulator.cpu.hires());
+        assert_eq!(emulator.cpu.pc, START_ADDRESS as u16);
+    }
+
+    #[test]
+    fn key_commands_update_the_keypad() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.handle(Command::Key(0xA, true));
+        assert!(emulator.cpu.keypad[0xA]);
+        emulator.handle(Command::Key(0xA, false));
+        assert!(!emulator.cpu.keypad[0xA]);
+    }
+
+    #[test]
+    fn watch_reloads_a_changed_rom() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watch.ch8");
+
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        emulator.set_watch(true);
+        emulator.load_rom(&path);
+        emulator.watch_rom();
+        assert_eq!(emulator.cpu.mem[START_ADDRESS..START_ADDRESS + 2], [0x12, 0x00]);
+
+        fs::write(&path, [0x13, 0x00]).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        emulator.watch_rom();
+
+        assert_eq!(emulator.cpu.mem[START_ADDRESS..START_ADDRESS + 2], [0x13, 0x00]);
+    }
+
+    #[test]
+    fn watch_applies_the_rom_database_to_the_reloaded_rom() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watch.ch8");
+
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        emulator.set_watch(true);
+        emulator.load_rom(&path);
+        assert_eq!((emulator.speed, emulator.core.quirks), (DEFAULT_SPEED, Quirks::original()));
+
+        fs::copy("tests/5-quirks.ch8", &path).unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        emulator.watch_rom();
+
+        assert_eq!((emulator.speed, emulator.core.quirks), (15, Platform::Chip8.quirks()));
+    }
+
+    #[test]
+    fn speed_steps() {
+        assert_eq!(faster(10), 15);
+        assert_eq!(faster(11), 15);
+        assert_eq!(faster(1000), 1000);
+        assert_eq!(slower(10), 7);
+        assert_eq!(slower(11), 10);
+        assert_eq!(slower(1), 1);
+    }
+
+    #[test]
+    fn step_runs_one_frame_while_paused() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+        emulator.set_speed(3);
+        emulator.cpu.delay_timer = 5;
+
+        emulator.handle(Command::Step);
+        assert_eq!(emulator.cpu.pc, START_ADDRESS as u16);
+
+        emulator.handle(Command::Pause);
+        emulator.handle(Command::Step);
+        assert_eq!(emulator.cpu.pc, START_ADDRESS as u16 + 6);
+        assert_eq!(emulator.cpu.delay_timer, 4);
+    }
+
+    #[test]
+    fn ticks_run_frames_only_from_the_sync_source() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+        emulator.set_speed(2);
+
+        emulator.handle(Command::Tick(Sync::Audio));
+        assert_eq!(emulator.cpu.pc, START_ADDRESS as u16);
+
+        emulator.set_sync(Sync::Audio);
+        emulator.handle(Command::Tick(Sync::Vsync));
+        assert_eq!(emulator.cpu.pc, START_ADDRESS as u16);
+        emulator.handle(Command::Tick(Sync::Audio));
+        assert_eq!(emulator.cpu.pc, START_ADDRESS as u16 + 4);
+    }
+
+    #[test]
+    fn the_profile_counts_on_across_resets_of_the_rom() {
+        let (tx, _rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+        emulator.set_profiler(Profiler::new());
+
+        emulator.load_rom(Path::new("tests/2-ibm-logo.ch8"));
+        emulator.set_speed(2);
+        emulator.run_frames(1);
+        emulator.reset();
+        emulator.run_frames(1);
+
+        let profiler = emulator.take_profiler().unwrap();
+        assert_eq!(profiler.executions(START_ADDRESS), 2);
+        assert_eq!(profiler.executions(START_ADDRESS + 4), 0);
+    }
+
+    #[test]
+    fn run_ends_on_quit() {
+        let (tx, _rx) = mpsc::channel();
+        let (cmd_tx, cmd_rx) = mpsc::channel();
+        let mut emulator = Emulator::new(tx);
+
+        emulator.handle(Command::Pause);
+        cmd_tx.send(Command::Quit).unwrap();
+        emulator.run(cmd_rx);
+    }
+}
@@ -0,0 +1,322 @@
+//! The display the opcodes draw on and the frontends show: any width and height, one to
+//! eight bitplanes, a bit per pixel and plane.
+//!
+//! Every row of a plane is packed in u64 words, the leftmost pixel in the highest bit of the
+//! first word. A 64x32 display is 256 bytes, which is what a frame costs on the channel, and
+//! a sprite byte is drawn with a shift, an XOR and an AND for the collision.
+
+const WORD: usize = u64::BITS as usize;
+
+#[derive(Clone, Debug)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    planes: usize,
+    /// Words per row.
+    stride: usize,
+    /// Plane after plane, row after row.
+    bits: Vec<u64>,
+    /// Set when a pixel changes, see take_dirty.
+    dirty: bool,
+}
+
+/// The same pixels, whatever was sent.
+impl PartialEq for FrameBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        (self.width, self.height, self.planes) == (other.width, other.height, other.planes)
+            && self.bits == other.bits
+    }
+}
+
+impl Eq for FrameBuffer {}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize, planes: usize) -> Self {
+        assert!((1..=8).contains(&planes), "Bug - {planes} bitplanes");
+        let stride = width.div_ceil(WORD);
+
+        Self { width, height, planes, stride, bits: vec![0; stride * height * planes], dirty: true }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes the size, the display is cleared.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Self::new(width, height, self.planes);
+    }
+
+    fn row(&self, plane: usize, y: usize) -> usize {
+        (plane * self.height + y) * self.stride
+    }
+
+    /// The word of the pixel at `x`, `y` of `plane`, and its bit.
+    fn at(&self, plane: usize, x: usize, y: usize) -> (usize, u64) {
+        debug_assert!(x < self.width && y < self.height, "Bug - pixel {x},{y} is off the display");
+        (self.row(plane, y) + x / WORD, 1 << (WORD - 1 - x % WORD))
+    }
+
+    /// The pixel at `x`, `y`: the mask of the planes it is set on, see Palette::color.
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        (0..self.planes).fold(0, |pixel, plane| {
+            let (word, bit) = self.at(plane, x, y);
+            pixel | u8::from(self.bits[word] & bit != 0) << plane
+        })
+    }
+
+    /// Sets the pixel at `x`, `y` on the planes of the mask `pixel`, clears it on the others.
+    pub fn set(&mut self, x: usize, y: usize, pixel: u8) {
+        for plane in 0..self.planes {
+            let (word, bit) = self.at(plane, x, y);
+            let on = pixel >> plane & 1 == 1;
+            if (self.bits[word] & bit != 0) != on {
+                self.bits[word] ^= bit;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// XORs the 8 pixels of a sprite byte onto `plane` from `x`, `y`. The pixels past the
+    /// right edge are clipped, or wrap to the left edge with `wrap`. Returns true when a pixel
+    /// was erased, a collision.
+    pub fn xor_byte(&mut self, plane: usize, x: usize, y: usize, byte: u8, wrap: bool) -> bool {
+        let past = (x + 8).saturating_sub(self.width);
+        let row = self.row(plane, y);
+        let mut collision = self.xor_bits(row, x, byte & (0xFF << past));
+
+        if wrap && past > 0 {
+            collision |= self.xor_bits(row, 0, byte << (8 - past));
+        }
+        collision
+    }
+
+    /// XORs the bits of `byte` from `x` of the row starting at the word `row`, the byte has
+    /// no pixel past the width.
+    fn xor_bits(&mut self, row: usize, x: usize, byte: u8) -> bool {
+        if byte == 0 {
+            return false;
+        }
+
+        // The byte at the top of a word shifted to x, what goes past the word in the next one.
+        let (word, shift) = (row + x / WORD, x % WORD);
+        let high = u64::from(byte) << (WORD - 8) >> shift;
+        let mut collision = self.bits[word] & high != 0;
+        self.bits[word] ^= high;
+
+        // Clipped bytes have nothing past the last word of the row.
+        let low = if shift > WORD - 8 { u64::from(byte) << (2 * WORD - 8 - shift) } else { 0 };
+        if low != 0 {
+            collision |= self.bits[word + 1] & low != 0;
+            self.bits[word + 1] ^= low;
+        }
+        self.dirty = true;
+        collision
+    }
+
+    pub fn clear(&mut self) {
+        if self.bits.iter().any(|&word| word != 0) {
+            self.bits.fill(0);
+            self.dirty = true;
+        }
+    }
+
+    /// The pixels row after row, see get.
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| self.get(x, y)))
+    }
+
+    /// True when a pixel changed since the last call, what the frames are sent on.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+}
+
+/// The scrolls of SUPER-CHIP and XO-CHIP.
+impl FrameBuffer {
+    /// Moves every row `n` rows down, the rows on top are cleared.
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.height);
+        for plane in 0..self.planes {
+            let (top, bottom) = (self.row(plane, 0), self.row(plane, self.height));
+            self.bits.copy_within(top..bottom - n * self.stride, top + n * self.stride);
+            self.bits[top..top + n * self.stride].fill(0);
+        }
+        self.dirty |= n > 0;
+    }
+
+    /// Moves every row `n` rows up, the rows at the bottom are cleared.
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.height);
+        for plane in 0..self.planes {
+            let (top, bottom) = (self.row(plane, 0), self.row(plane, self.height));
+            self.bits.copy_within(top + n * self.stride..bottom, top);
+            self.bits[bottom - n * self.stride..bottom].fill(0);
+        }
+        self.dirty |= n > 0;
+    }
+
+    /// Moves every pixel `n` columns right, the columns on the left are cleared.
+    pub fn scroll_right(&mut self, n: usize) {
+        self.shift_rows(|row| Self::shift_right(row, n));
+    }
+
+    /// Moves every pixel `n` columns left, the columns on the right are cleared.
+    pub fn scroll_left(&mut self, n: usize) {
+        self.shift_rows(|row| Self::shift_left(row, n));
+    }
+
+    fn shift_rows(&mut self, shift: impl Fn(&mut [u64])) {
+        // The bits past the width, in the last word, stay clear.
+        let tail = match self.width % WORD {
+            0 => u64::MAX,
+            bits => !(u64::MAX >> bits),
+        };
+
+        for row in self.bits.chunks_exact_mut(self.stride) {
+            shift(row);
+            if let Some(last) = row.last_mut() {
+                *last &= tail;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Shifts a packed row `n` bits towards the end. The words are read before they are written.
+    fn shift_right(row: &mut [u64], n: usize) {
+        let (words, bits) = (n / WORD, n % WORD);
+
+        for i in (0..row.len()).rev() {
+            let high = if i >= words { row[i - words] } else { 0 };
+            let low = if i > words { row[i - words - 1] } else { 0 };
+            row[i] = if bits == 0 { high } else { high >> bits | low << (WORD - bits) };
+        }
+    }
+
+    /// Shifts a packed row `n` bits towards the start.
+    fn shift_left(row: &mut [u64], n: usize) {
+        let (words, bits) = (n / WORD, n % WORD);
+
+        for i in 0..row.len() {
+            let high = row.get(i + words).copied().unwrap_or(0);
+            let low = row.get(i + words + 1).copied().unwrap_or(0);
+            row[i] = if bits == 0 { high } else { high << bits | low >> (WORD - bits) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flips the pixel at `x`, `y` of `plane`. Returns true when it was set, a collision.
+    fn toggle(fb: &mut FrameBuffer, plane: usize, x: usize, y: usize) -> bool {
+        let (word, bit) = fb.at(plane, x, y);
+        let was_set = fb.bits[word] & bit != 0;
+
+        fb.bits[word] ^= bit;
+        was_set
+    }
+
+    fn lit(fb: &FrameBuffer) -> Vec<(usize, usize)> {
+        let width = fb.width();
+        fb.pixels().enumerate().filter(|&(_, p)| p != 0).map(|(i, _)| (i % width, i / width)).collect()
+    }
+
+    #[test]
+    fn pixels_are_plane_masks() {
+        let mut fb = FrameBuffer::new(100, 3, 2);
+
+        fb.set(70, 1, 3);
+        assert!(toggle(&mut fb, 1, 70, 1));
+        toggle(&mut fb, 1, 99, 2);
+
+        assert_eq!(fb.get(70, 1), 1);
+        assert_eq!(fb.get(99, 2), 2);
+        assert_eq!(lit(&fb), [(70, 1), (99, 2)]);
+    }
+
+    /// What xor_byte does, pixel by pixel.
+    fn xor_pixels(fb: &mut FrameBuffer, x: usize, y: usize, byte: u8, wrap: bool) -> bool {
+        let mut collision = false;
+        for i in 0..8 {
+            let col = x + i;
+            if col >= fb.width() && !wrap {
+                break;
+            }
+            if byte >> (7 - i) & 1 == 1 {
+                collision |= toggle(fb, 0, col % fb.width(), y);
+            }
+        }
+        collision
+    }
+
+    #[test]
+    fn sprite_bytes_draw_as_pixel_by_pixel() {
+        for width in [64, 100, 128] {
+            for wrap in [false, true] {
+                let (mut packed, mut pixels) = (FrameBuffer::new(width, 2, 1), FrameBuffer::new(width, 2, 1));
+                for x in 0..width {
+                    for byte in [0xFF, 0xA5, 0x81, 0x3C, 0x01] {
+                        let y = x % 2;
+                        assert_eq!(
+                            packed.xor_byte(0, x, y, byte, wrap),
+                            xor_pixels(&mut pixels, x, y, byte, wrap),
+                            "{byte:02X} at {x} of {width}, wrap {wrap}"
+                        );
+                        assert_eq!(packed, pixels, "{byte:02X} at {x} of {width}, wrap {wrap}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_changes_are_dirty() {
+        let mut fb = FrameBuffer::new(64, 32, 1);
+        assert!(fb.take_dirty());
+
+        fb.set(1, 1, 0);
+        fb.clear();
+        assert!(!fb.take_dirty());
+
+        fb.set(1, 1, 1);
+        assert!(fb.take_dirty());
+        assert!(!fb.take_dirty());
+    }
+
+    #[test]
+    fn rows_scroll_up_and_down() {
+        let mut fb = FrameBuffer::new(128, 64, 2);
+        fb.set(127, 0, 2);
+        fb.set(0, 63, 1);
+
+        fb.scroll_down(4);
+        assert_eq!(lit(&fb), [(127, 4)]);
+
+        fb.scroll_up(5);
+        assert_eq!(lit(&fb), []);
+    }
+
+    #[test]
+    fn columns_scroll_across_the_words() {
+        let mut fb = FrameBuffer::new(100, 1, 1);
+        fb.set(0, 0, 1);
+        fb.set(62, 0, 1);
+        fb.set(98, 0, 1);
+
+        fb.scroll_right(4);
+        assert_eq!(lit(&fb), [(4, 0), (66, 0)]);
+
+        fb.scroll_left(70);
+        assert_eq!(lit(&fb), []);
+
+        fb.set(99, 0, 1);
+        fb.scroll_left(65);
+        assert_eq!(lit(&fb), [(34, 0)]);
+    }
+}
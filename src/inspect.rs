@@ -0,0 +1,111 @@
+//! `oxidate inspect rom.ch8`: what a ROM needs, told from its code.
+
+use crate::analysis::Analysis;
+use crate::quirks::Platform;
+use crate::rom_db::RomDb;
+use crate::rom_file;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::Path;
+
+/// How many addresses are listed for every finding.
+const MAX_ADDRESSES: usize = 8;
+
+pub fn run(rom: &Path) -> Result<()> {
+    let file = rom_file::read(rom).with_context(|| format!("Cannot read {}", rom.display()))?;
+    print!("{}", report(&file.data, &RomDb::load()));
+    Ok(())
+}
+
+fn report(rom: &[u8], db: &RomDb) -> String {
+    let analysis = Analysis::new(rom);
+    let sha1 = crate::cpu::sha1(rom);
+    let code: u16 = analysis.instructions.values().map(|i| i.len).sum();
+
+    let mut out = format!("{} bytes, SHA-1 {sha1}\n", rom.len());
+    out += &format!(
+        "Reachable code: {code} bytes, {} instructions, {} subroutines\n",
+        analysis.instructions.len(),
+        analysis.subroutines.len()
+    );
+
+    out += "\nOpcodes:\n";
+    for (family, addrs) in analysis.families() {
+        out += &format!("  {:<17} {:>5}  {}\n", family.name(), addrs.len(), addresses(&addrs));
+    }
+
+    let patched = analysis.patched();
+    if !patched.is_empty() {
+        let patched: Vec<u16> = patched.into_iter().collect();
+        out += &format!("\nSelf-modifying code: {}\n", addresses(&patched));
+    }
+
+    let quirks = analysis.quirk_uses();
+    if !quirks.is_empty() {
+        out += "\nQuirk dependent code:\n";
+        for q in &quirks {
+            out += &format!("  {:<10} {:03X}  {}\n", q.quirk, q.addr, q.note);
+        }
+    }
+
+    let platform = analysis.platform();
+    out += &format!("\nSuggested: --platform {}\n", name(platform));
+    if let Some(entry) = db.get(&sha1) {
+        let known = entry.platform().map_or("unknown".into(), name);
+        out += &format!("ROM database: {} (--platform {known})\n", entry.title);
+    }
+
+    out
+}
+
+/// The `--platform` value of a platform.
+fn name(platform: Platform) -> String {
+    platform.to_possible_value().expect("Bug - every platform has a value").get_name().to_string()
+}
+
+/// The first addresses of a list, in hex.
+fn addresses(addrs: &[u16]) -> String {
+    let mut list: Vec<String> = addrs.iter().take(MAX_ADDRESSES).map(|a| format!("{a:03X}")).collect();
+    if addrs.len() > MAX_ADDRESSES {
+        list.push("...".into());
+    }
+    list.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_suggests_the_platform_and_names_the_known_rom() {
+        let ibm = std::fs::read("tests/2-ibm-logo.ch8").unwrap();
+        let report = report(&ibm, &RomDb::bundled());
+
+        assert_eq!(
+            report,
+            "132 bytes, SHA-1 b9bbc12cee3f7b9d3b1f69161f7d7a2d86953379\n\
+             Reachable code: 42 bytes, 21 instructions, 0 subroutines\n\
+             \n\
+             Opcodes:\n  \
+             CHIP-8               21  200 202 204 206 208 20A 20C 20E ...\n\
+             \n\
+             Suggested: --platform modern\n\
+             ROM database: IBM Logo (--platform chip8)\n"
+        );
+    }
+
+    #[test]
+    fn report_finds_the_extensions_self_modifying_code_and_quirks() {
+        let rom = std::fs::read("tests/8-scrolling.ch8").unwrap();
+        let report = report(&rom, &RomDb::default());
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(lines[1], "Reachable code: 870 bytes, 435 instructions, 4 subroutines");
+        assert_eq!(lines[4], "  CHIP-8              367  200 202 204 206 208 20A 20C 20E ...");
+        assert_eq!(lines[5], "  SCHIP                62  324 3D4 3D6 3D8 3DA 3DC 3DE 3E8 ...");
+        assert_eq!(lines[6], "  XO-CHIP               3  52E 598 59A");
+        assert!(lines.contains(&"Self-modifying code: 222 282 29A"), "{report}");
+        assert!(lines.contains(&"  load/store 298  F155 moves I or not, FC65 at 29C uses it"), "{report}");
+        assert_eq!(lines.last(), Some(&"Suggested: --platform xochip"));
+    }
+}
@@ -0,0 +1,163 @@
+//! The instructions as CpuCore decodes them, once per memory word.
+//!
+//! An instruction is a byte: the operands stay in the opcode it was decoded from, which the
+//! op_ functions take apart themselves. Cpu keeps the decoded instruction of every address
+//! until the word is written, see Cpu::invalidate.
+
+/// What an opcode runs, on the platform and in the mode it was decoded in. The comments are
+/// the opcodes decoded to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn
+    Sys,
+    /// 00E0, 0230 on the hi-res display
+    Cls,
+    /// 00D4
+    MachineRet,
+    /// 00EE
+    Ret,
+    /// 1nnn
+    Jp,
+    /// 2nnn
+    Call,
+    /// 3xkk
+    SeByte,
+    /// 4xkk
+    SneByte,
+    /// 5xy0
+    SeReg,
+    /// 6xkk
+    LdByte,
+    /// 7xkk
+    AddByte,
+    /// 8xy0
+    LdReg,
+    /// 8xy1
+    Or,
+    /// 8xy2
+    And,
+    /// 8xy3
+    Xor,
+    /// 8xy4
+    AddReg,
+    /// 8xy5
+    Sub,
+    /// 8xy6
+    Shr,
+    /// 8xy7
+    Subn,
+    /// 8xyE
+    Shl,
+    /// 9xy0
+    SneReg,
+    /// Annn
+    LdI,
+    /// Bnnn
+    JpV0,
+    /// Cxkk
+    Rnd,
+    /// Dxyn
+    Drw,
+    /// Ex9E
+    Skp,
+    /// ExA1
+    Sknp,
+    /// Fx07
+    LdVxDt,
+    /// Fx0A
+    LdVxK,
+    /// Fx15
+    LdDtVx,
+    /// Fx18
+    LdStVx,
+    /// Fx1E
+    AddIVx,
+    /// Fx29
+    LdF,
+    /// Fx33
+    LdB,
+    /// Fx55
+    Store,
+    /// Fx65
+    Load,
+    /// CHIP-8X 02A0
+    Background,
+    /// CHIP-8X 5xy1
+    AddNibbles,
+    /// CHIP-8X BxyN
+    Colour,
+    /// CHIP-8X ExF2
+    Skp2,
+    /// CHIP-8X ExF5
+    Sknp2,
+    /// CHIP-8X FxF8
+    Pitch,
+    /// MegaChip 0010 / 0011
+    MegaMode,
+    /// MegaChip 00E0
+    MegaCls,
+    /// MegaChip 01nn nnnn
+    LdILong,
+    /// MegaChip 02nn
+    Palette,
+    /// MegaChip 03nn
+    SpriteWidth,
+    /// MegaChip 04nn
+    SpriteHeight,
+    /// MegaChip 05nn
+    Alpha,
+    /// MegaChip 060n
+    PlaySample,
+    /// MegaChip 0700
+    StopSample,
+    /// MegaChip 080n
+    Blend,
+    /// MegaChip 09nn
+    Collision,
+    /// MegaChip Dxyn
+    MegaDrw,
+    /// SUPER-CHIP 00Cn
+    ScrollDown,
+    /// SUPER-CHIP 00FB
+    ScrollRight,
+    /// SUPER-CHIP 00FC
+    ScrollLeft,
+    /// SUPER-CHIP 00FD
+    Exit,
+    /// SUPER-CHIP 00FE
+    Low,
+    /// SUPER-CHIP 00FF
+    High,
+    /// SUPER-CHIP Fx30
+    LdHf,
+    /// SUPER-CHIP Fx75
+    SaveFlags,
+    /// SUPER-CHIP Fx85
+    LoadFlags,
+    /// XO-CHIP 00Dn
+    ScrollUp,
+    /// XO-CHIP 5xy2
+    SaveRange,
+    /// XO-CHIP 5xy3
+    LoadRange,
+    /// XO-CHIP F000 nnnn
+    LdIWord,
+    /// XO-CHIP Fx01
+    Plane,
+    /// XO-CHIP F002
+    Audio,
+    /// XO-CHIP Fx3A
+    AudioPitch,
+    /// No instruction on the platform.
+    Unknown,
+}
+
+impl Instruction {
+    /// The bytes of the instruction: 4 for the loads of I followed by the address.
+    pub(crate) fn len(self) -> u16 {
+        match self {
+            Instruction::LdILong | Instruction::LdIWord => 4,
+            _ => 2,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+/// Maps keyboard keys to the 16 keys of the CHIP-8 hex keypad.
+///
+/// The default layout is the usual one, the left side of a QWERTY keyboard:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  ->  Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    // keys[n] is the keyboard key for the CHIP-8 key n
+    keys: [char; 16],
+}
+
+const DEFAULT_LAYOUT: &str = "x123qweasdzc4rfv";
+/// The second CHIP-8X keypad, on the right side of the keyboard:
+///
+/// ```text
+/// 1 2 3 C      6 7 8 9
+/// 4 5 6 D  ->  Y U I O
+/// 7 8 9 E      H J K L
+/// A 0 B F      N M , .
+/// ```
+const SECOND_LAYOUT: &str = "m678yuihjkn,9ol.";
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_layout(DEFAULT_LAYOUT).unwrap()
+    }
+}
+
+impl Keymap {
+    /// Builds a keymap from the 16 keyboard keys bound to the CHIP-8 keys 0 to F.
+    pub fn from_layout(layout: &str) -> Option<Self> {
+        let chars: Vec<char> = layout.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+        Some(Self { keys: chars.try_into().ok()? })
+    }
+
+    /// The default mapping of the second CHIP-8X keypad.
+    pub fn second() -> Self {
+        Self::from_layout(SECOND_LAYOUT).unwrap()
+    }
+
+    /// The CHIP-8 key bound to a keyboard key.
+    pub fn key(&self, c: char) -> Option<u8> {
+        let c = c.to_ascii_lowercase();
+        self.keys.iter().position(|&k| k == c).map(|k| k as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_layout() {
+        let keymap = Keymap::default();
+
+        assert_eq!(keymap.key('1'), Some(0x1));
+        assert_eq!(keymap.key('4'), Some(0xC));
+        assert_eq!(keymap.key('X'), Some(0x0));
+        assert_eq!(keymap.key('v'), Some(0xF));
+        assert_eq!(keymap.key('p'), None);
+    }
+
+    #[test]
+    fn the_keypads_do_not_overlap() {
+        let (first, second) = (Keymap::default(), Keymap::second());
+
+        assert_eq!(second.key('6'), Some(0x1));
+        assert_eq!(second.key('.'), Some(0xF));
+        assert!(first.keys.iter().all(|&c| second.key(c).is_none()));
+    }
+
+    #[test]
+    fn from_layout_needs_16_keys() {
+        assert!(Keymap::from_layout("123").is_none());
+        assert_eq!(Keymap::from_layout("0123456789abcdef").unwrap().key('b'), Some(0xB));
+    }
+}
@@ -0,0 +1,38 @@
+//! The emulator, its frontends and its tools. The oxidate binary runs the command line.
+
+mod analysis;
+mod audio;
+mod cdp1802;
+mod cfg;
+mod chip8x;
+mod cli;
+mod clock;
+mod config;
+mod control;
+pub mod cpu;
+pub mod cpu_core;
+#[allow(dead_code)]
+mod cpu_debugger;
+mod cpu_opcode;
+mod disasm;
+mod emulator;
+pub mod frame_buffer;
+mod inspect;
+pub mod instruction;
+mod keypad;
+mod machine_code;
+mod megachip;
+mod palette;
+mod profiler;
+pub mod quirks;
+mod render_filter;
+mod rom_browser;
+mod rom_db;
+mod rom_file;
+mod settings;
+mod terminal_video;
+mod translator;
+mod video_input;
+mod vip;
+
+pub use cli::run;
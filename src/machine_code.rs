@@ -0,0 +1,221 @@
+//! The 0nnn calls to CDP1802 machine code, as the COSMAC VIP interpreter makes them.
+//!
+//! The routine runs on a CDP1802 sharing the CHIP-8 memory, which is laid out like the one of a
+//! 4 KiB VIP for the time of the call: V0-VF at 0EF0, the display at 0F00, the stack below
+//! 0ED0. The 64x64 display of the hi-res variant starts at 0E00, the registers and the stack
+//! move down with it. What the program kept there is put back after the call. The registers
+//! are the interpreter ones: R5 is the CHIP-8 program counter, R6 and R7 point to Vx and Vy,
+//! R8 holds the timers and RA is I. The routine returns with `D4`, SEP R4, which jumps back to
+//! the interpreter loop.
+
+use crate::cdp1802::{Bus, Cdp1802};
+use crate::config::W;
+use crate::cpu::Cpu;
+use crate::cpu_core::MEMORY_SIZE;
+use crate::frame_buffer::FrameBuffer;
+use anyhow::{bail, Result};
+
+/// Where the display ends, at the top of the memory of a 4 KiB VIP.
+const DISPLAY_END: usize = 0x1000;
+/// From the display to V0, and from V0 to the top of the stack.
+const REGISTERS_BELOW: usize = 0x10;
+const STACK_BELOW: usize = 0x21;
+/// A second of the 1.76 MHz VIP: a routine running longer does not return.
+const MAX_CYCLES: u64 = 220_080;
+
+/// The CHIP-8 memory and keypad, as the 1802 sees them.
+struct CpuBus<'a> {
+    mem: &'a mut [u8],
+    /// The key EF3 reports, latched by OUT 2.
+    key_latch: u8,
+}
+
+impl Bus for CpuBus<'_> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.mem[usize::from(addr) % MEMORY_SIZE]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.mem[usize::from(addr) % MEMORY_SIZE] = value;
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        if port == 2 {
+            self.key_latch = value & 0xF;
+        }
+    }
+}
+
+/// Runs the routine at `addr` for the `0nnn` at the program counter, then takes the registers,
+/// the timers and the display back from where the routine left them. When the routine does not
+/// return the CHIP-8 state is left as it was, only the memory outside the work area keeps what
+/// the routine wrote.
+pub fn call(cpu: &mut Cpu, addr: u16) -> Result<()> {
+    let display = DISPLAY_END - W * cpu.height() / 8;
+    let registers = display - REGISTERS_BELOW;
+    let saved = cpu.mem[registers..DISPLAY_END].to_vec();
+
+    let pixels = pack(&cpu.vram);
+    cpu.mem[display..DISPLAY_END].copy_from_slice(&pixels);
+    cpu.mem[registers..registers + 16].copy_from_slice(&cpu.v);
+
+    let mut core = Cdp1802::new();
+    core.r[2] = (registers - STACK_BELOW) as u16;
+    core.r[3] = addr;
+    core.r[5] = cpu.pc.wrapping_add(2);
+    core.r[6] = registers as u16 + (addr >> 8);
+    core.r[7] = registers as u16 + ((addr >> 4) & 0xF);
+    core.r[8] = u16::from_be_bytes([cpu.delay_timer, cpu.sound_timer]);
+    core.r[0xA] = cpu.i as u16;
+    core.r[0xB] = display as u16;
+    core.p = 3;
+    core.x = 2;
+
+    let returned = run(&mut core, cpu, addr);
+    if returned.is_ok() {
+        cpu.v.copy_from_slice(&cpu.mem[registers..registers + 16]);
+        cpu.i = u32::from(core.r[0xA]) % MEMORY_SIZE as u32;
+        cpu.pc = core.r[5].wrapping_sub(2) % MEMORY_SIZE as u16;
+        [cpu.delay_timer, cpu.sound_timer] = core.r[8].to_be_bytes();
+        if cpu.mem[display..DISPLAY_END] != pixels {
+            unpack(&cpu.mem[display..DISPLAY_END], &mut cpu.vram);
+            cpu.draw_flag = true;
+        }
+    }
+
+    cpu.mem[registers..DISPLAY_END].copy_from_slice(&saved);
+    returned
+}
+
+/// Steps the 1802 until the routine returns with SEP R4.
+fn run(core: &mut Cdp1802, cpu: &mut Cpu, addr: u16) -> Result<()> {
+    let mut bus = CpuBus { mem: &mut cpu.mem, key_latch: 0 };
+
+    while core.p != 4 {
+        // Nothing interrupts the idle 1802 during the call.
+        if core.idle {
+            bail!("The machine code at {addr:03X} stopped on IDL instead of returning to CHIP-8");
+        }
+        if core.cycles > MAX_CYCLES {
+            bail!("The machine code at {addr:03X} did not return to CHIP-8 with D4 (SEP R4)");
+        }
+        core.ef[2] = cpu.keypad[usize::from(bus.key_latch)];
+        core.step(&mut bus);
+    }
+    Ok(())
+}
+
+/// The display as the VIP keeps it, a bit per pixel.
+fn pack(vram: &FrameBuffer) -> Vec<u8> {
+    let mut display = vec![0; W * vram.height() / 8];
+    for i in 0..W * vram.height() {
+        display[i / 8] |= u8::from(vram.get(i % W, i / W) != 0) << (7 - i % 8);
+    }
+    display
+}
+
+fn unpack(display: &[u8], vram: &mut FrameBuffer) {
+    for i in 0..W * vram.height() {
+        vram.set(i % W, i / W, (display[i / 8] >> (7 - i % 8)) & 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu(routine: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.mem[0x300..0x300 + routine.len()].copy_from_slice(routine);
+        cpu
+    }
+
+    #[test]
+    fn routines_see_the_registers_where_the_vip_keeps_them() {
+        let mut cpu = cpu(&[
+            0x46,       // LDA 6        D = V3, R6 = V4
+            0xFC, 0x01, // ADI 01
+            0x56,       // STR 6        V4 = V3 + 1
+            0x8A,       // GLO A
+            0xA8,       // PLO 8        the sound timer = I & FF
+            0x15,       // INC 5
+            0x15,       // INC 5        skips the next CHIP-8 instruction
+            0xD4,       // SEP 4
+        ]);
+        cpu.v[3] = 0x41;
+        cpu.i = 0x234;
+        cpu.delay_timer = 9;
+
+        call(&mut cpu, 0x300).unwrap();
+
+        assert_eq!(cpu.v[3], 0x41);
+        assert_eq!(cpu.v[4], 0x42);
+        assert_eq!(cpu.sound_timer, 0x34);
+        assert_eq!(cpu.delay_timer, 9);
+        assert_eq!(cpu.i, 0x234);
+        // The tick adds 2, to 0204.
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn routines_draw_on_the_display_page() {
+        let mut cpu = cpu(&[
+            0xF8, 0xFF, // LDI FF
+            0x5B,       // STR B        the first 8 pixels
+            0xD4,       // SEP 4
+        ]);
+
+        call(&mut cpu, 0x300).unwrap();
+
+        assert!(cpu.draw_flag);
+        assert_eq!(cpu.vram.pixels().take(9).collect::<Vec<_>>(), [1, 1, 1, 1, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn routines_that_do_not_return_are_errors() {
+        let mut cpu = cpu(&[0x30, 0x00]); // BR 00
+        cpu.v[1] = 7;
+
+        assert!(call(&mut cpu, 0x300).is_err());
+        assert_eq!((cpu.pc, cpu.v[1]), (0x200, 7));
+
+        // 0000 in zeroed memory: IDL.
+        assert!(call(&mut cpu, 0x000).is_err());
+    }
+
+    #[test]
+    fn the_work_area_is_put_back() {
+        let mut cpu = cpu(&[0xD4]); // SEP 4
+        for (i, byte) in cpu.mem[0xEF0..].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let before = cpu.mem.clone();
+        cpu.v[2] = 0x99;
+
+        call(&mut cpu, 0x300).unwrap();
+
+        assert_eq!(cpu.mem, before);
+    }
+
+    #[test]
+    fn routines_draw_on_the_whole_hires_display() {
+        let mut cpu = cpu(&[
+            0xF8, 0xFF, // LDI FF
+            0x5B,       // STR B        the first 8 pixels, at 0E00
+            0xF8, 0x0F, // LDI 0F
+            0xBB,       // PHI B
+            0xF8, 0xFF, // LDI FF
+            0xAB,       // PLO B
+            0xF8, 0x01, // LDI 01
+            0x5B,       // STR B        the last pixel, at 0FFF
+            0xD4,       // SEP 4
+        ]);
+        cpu.set_hires();
+
+        call(&mut cpu, 0x300).unwrap();
+
+        assert_eq!(cpu.vram.pixels().filter(|&p| p != 0).count(), 9);
+        assert_eq!(cpu.vram.get(0, 0), 1);
+        assert_eq!(cpu.vram.get(W - 1, 63), 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! MegaChip, the CHIP-8 extension of Revival Studios with a 256x192 true colour display and
+//! digitised sound.
+//!
+//! A MegaChip program switches to its mode with 0011. Its sprites are bytes, indexes in a
+//! palette of ARGB colours it loads with 02nn, index 0 is transparent. The display only
+//! changes on 00E0, which shows what was drawn since the previous one and starts over.
+//! 01nn nnnn points I anywhere in 16 MiB, where the bitmaps and the samples are.
+
+use crate::cpu::FONT;
+use crate::palette::Rgba;
+
+pub const W: usize = 256;
+pub const H: usize = 192;
+/// What 01nn nnnn addresses.
+pub const MEMORY_SIZE: usize = 0x100_0000;
+
+const CLEAR: Rgba = [0x00, 0x00, 0x00, 0xFF];
+/// The palette index the font sprites are drawn with.
+const FONT_INDEX: u8 = 0xFF;
+/// The sample header: the rate on 2 bytes, the length on 3, then a 0.
+const SAMPLE_HEADER: usize = 6;
+
+/// How 080n mixes the sprites with what is below them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Blend {
+    #[default]
+    Normal,
+    Percent25,
+    Percent50,
+    Percent75,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    /// The blend mode n of 080n, Normal for the unknown ones.
+    fn from_mode(n: u8) -> Self {
+        match n {
+            1 => Blend::Percent25,
+            2 => Blend::Percent50,
+            3 => Blend::Percent75,
+            4 => Blend::Add,
+            5 => Blend::Multiply,
+            _ => Blend::Normal,
+        }
+    }
+
+    /// A colour component of a sprite drawn over `dst` with `alpha`, 0 - 255.
+    fn mix(self, dst: u8, src: u8, alpha: u32) -> u8 {
+        let (dst, src) = (u32::from(dst), u32::from(src));
+        let colour = match self {
+            Blend::Add => (dst + src).min(0xFF),
+            Blend::Multiply => dst * src / 0xFF,
+            _ => src,
+        };
+        let alpha = match self {
+            Blend::Percent25 => alpha / 4,
+            Blend::Percent50 => alpha / 2,
+            Blend::Percent75 => alpha * 3 / 4,
+            _ => alpha,
+        };
+
+        ((colour * alpha + dst * (0xFF - alpha)) / 0xFF) as u8
+    }
+}
+
+/// Unsigned 8-bit mono samples played at `rate` Hz.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub rate: u32,
+    pub data: Vec<u8>,
+    /// 0600 plays the sample over and over, 0601 once.
+    pub looped: bool,
+}
+
+/// What the sound output has to do, see MegaChip::sound.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Play(Sample),
+    Stop,
+}
+
+/// The MegaChip display and sound state, kept by Cpu on the MegaChip platform.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MegaChip {
+    /// Set by 0011, cleared by 0010. Otherwise the program runs as a SUPER-CHIP one.
+    pub enabled: bool,
+    palette: [Rgba; 256],
+    sprite_w: usize,
+    sprite_h: usize,
+    /// Set by 05nn, how opaque the sprites are drawn.
+    alpha: u8,
+    blend: Blend,
+    /// Drawing over a pixel of this palette index sets VF.
+    collision: u8,
+    /// The palette index of every pixel, what the collisions are checked against.
+    indexes: Vec<u8>,
+    /// The frame being drawn, RGBA.
+    rgba: Vec<u8>,
+    /// Set by 060n and 0700, taken by the emulator for the sound output.
+    pub sound: Option<Sound>,
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        let mut palette = [[0xFF; 4]; 256];
+        palette[0] = [0; 4];
+
+        Self {
+            enabled: false,
+            palette,
+            sprite_w: 0,
+            sprite_h: 0,
+            alpha: 0xFF,
+            blend: Blend::Normal,
+            collision: 0,
+            indexes: vec![0; W * H],
+            rgba: CLEAR.repeat(W * H),
+            sound: None,
+        }
+    }
+}
+
+impl MegaChip {
+    /// 02nn: the nn ARGB colours at `i` become the palette entries 1 to nn.
+    pub fn load_palette(&mut self, mem: &[u8], i: usize, nn: u8) {
+        for entry in 1..=usize::from(nn) {
+            let at = i + (entry - 1) * 4;
+            let [a, r, g, b] = std::array::from_fn(|c| mem[(at + c) % mem.len()]);
+            self.palette[entry] = [r, g, b, a];
+        }
+    }
+
+    /// 03nn, 0 is 256 pixels.
+    pub fn set_sprite_width(&mut self, nn: u8) {
+        self.sprite_w = if nn == 0 { 256 } else { usize::from(nn) };
+    }
+
+    /// 04nn, 0 is 256 pixels.
+    pub fn set_sprite_height(&mut self, nn: u8) {
+        self.sprite_h = if nn == 0 { 256 } else { usize::from(nn) };
+    }
+
+    /// 05nn
+    pub fn set_alpha(&mut self, nn: u8) {
+        self.alpha = nn;
+    }
+
+    /// 080n
+    pub fn set_blend(&mut self, n: u8) {
+        self.blend = Blend::from_mode(n);
+    }
+
+    /// 09nn
+    pub fn set_collision(&mut self, nn: u8) {
+        self.collision = nn;
+    }
+
+    /// Dxyn: draws the sprite at `i` at (`x`, `y`), clipped by the display edges. Returns
+    /// true when it covered a pixel of the collision colour. The font sprites stay 8 pixels
+    /// wide and `n` high, drawn with the last palette entry.
+    pub fn draw(&mut self, mem: &[u8], i: usize, x: u8, y: u8, n: u8) -> bool {
+        let at = |offset: usize| mem[(i + offset) % mem.len()];
+
+        if i < FONT.len() {
+            let pixel = |row, col| if at(row) >> (7 - col) & 1 == 1 { FONT_INDEX } else { 0 };
+            self.draw_pixels(x, y, 8, usize::from(n), pixel)
+        } else {
+            let width = self.sprite_w;
+            self.draw_pixels(x, y, width, self.sprite_h, |row, col| at(row * width + col))
+        }
+    }
+
+    fn draw_pixels(&mut self, x: u8, y: u8, w: usize, h: usize, index: impl Fn(usize, usize) -> u8) -> bool {
+        let (x, y) = (usize::from(x), usize::from(y));
+        let mut collision = false;
+
+        for row in 0..h.min(H.saturating_sub(y)) {
+            for col in 0..w.min(W - x) {
+                let index = index(row, col);
+                if index == 0 {
+                    continue;
+                }
+
+                let pixel = (y + row) * W + x + col;
+                collision |= self.indexes[pixel] != 0 && self.indexes[pixel] == self.collision;
+                self.indexes[pixel] = index;
+
+                let colour = self.palette[usize::from(index)];
+                let alpha = u32::from(colour[3]) * u32::from(self.alpha) / 0xFF;
+                let dst = &mut self.rgba[pixel * 4..pixel * 4 + 3];
+                for (dst, &src) in dst.iter_mut().zip(&colour[..3]) {
+                    *dst = self.blend.mix(*dst, src, alpha);
+                }
+            }
+        }
+        collision
+    }
+
+    /// 00E0: returns the frame drawn, RGBA, and starts a black one.
+    pub fn clear(&mut self) -> Vec<u8> {
+        self.indexes.fill(0);
+        std::mem::replace(&mut self.rgba, CLEAR.repeat(W * H))
+    }
+
+    /// 060n: plays the sample at `i`, over and over when n is 0.
+    pub fn play(&mut self, mem: &[u8], i: usize, n: u8) {
+        let at = |offset: usize| mem[(i + offset) % mem.len()];
+        let rate = u32::from(u16::from_be_bytes([at(0), at(1)]));
+        let len = u32::from_be_bytes([0, at(2), at(3), at(4)]) as usize;
+        let data = (SAMPLE_HEADER..SAMPLE_HEADER + len).map(at).collect();
+
+        self.sound = Some(Sound::Play(Sample { rate, data, looped: n == 0 }));
+    }
+
+    /// 0700
+    pub fn stop(&mut self) {
+        self.sound = Some(Sound::Stop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(frame: &[u8], x: usize, y: usize) -> &[u8] {
+        &frame[(y * W + x) * 4..(y * W + x + 1) * 4]
+    }
+
+    #[test]
+    fn sprites_are_palette_indexes() {
+        let mut mega = MegaChip::default();
+        let mut mem = vec![0; 0x1000];
+        // Two ARGB colours, then a 2x2 sprite with a transparent pixel.
+        mem[0x300..0x308].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF]);
+        mem[0x400..0x404].copy_from_slice(&[1, 2, 0, 1]);
+        mega.load_palette(&mem, 0x300, 2);
+        mega.set_sprite_width(2);
+        mega.set_sprite_height(2);
+
+        assert!(!mega.draw(&mem, 0x400, 10, 20, 0));
+        let frame = mega.clear();
+
+        assert_eq!(pixel(&frame, 10, 20), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(&frame, 11, 20), [0x00, 0x00, 0xFF, 0xFF]);
+        assert_eq!(pixel(&frame, 10, 21), CLEAR);
+        assert_eq!(pixel(&mega.clear(), 10, 20), CLEAR);
+    }
+
+    #[test]
+    fn drawing_over_the_collision_colour_collides() {
+        let mut mega = MegaChip::default();
+        let mut mem = vec![0; 0x1000];
+        mem[0x400] = 3;
+        mega.set_sprite_width(1);
+        mega.set_sprite_height(1);
+        mega.set_collision(3);
+
+        assert!(!mega.draw(&mem, 0x400, 255, 191, 0));
+        assert!(mega.draw(&mem, 0x400, 255, 191, 0));
+    }
+
+    #[test]
+    fn blending_mixes_with_the_pixels_below() {
+        assert_eq!(Blend::Normal.mix(0x00, 0xFF, 0xFF), 0xFF);
+        assert_eq!(Blend::Percent50.mix(0x00, 0xFF, 0xFF), 0x7F);
+        assert_eq!(Blend::Add.mix(0x80, 0x80, 0xFF), 0xFF);
+        assert_eq!(Blend::Multiply.mix(0x80, 0x80, 0xFF), 0x40);
+        assert_eq!(Blend::Normal.mix(0x10, 0xFF, 0x00), 0x10);
+    }
+
+    #[test]
+    fn samples_have_a_header() {
+        let mut mega = MegaChip::default();
+        let mut mem = vec![0; 0x1000];
+        mem[0x400..0x409].copy_from_slice(&[0x1F, 0x40, 0x00, 0x00, 0x03, 0x00, 0x80, 0xFF, 0x00]);
+
+        mega.play(&mem, 0x400, 1);
+
+        let sample = Sample { rate: 8000, data: vec![0x80, 0xFF, 0x00], looped: false };
+        assert_eq!(mega.sound, Some(Sound::Play(sample)));
+    }
+}
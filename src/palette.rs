@@ -0,0 +1,152 @@
+use anyhow::{bail, Context, Result};
+
+pub type Rgba = [u8; 4];
+
+/// A display palette.
+/// Index 0 is the background, index 1 the first bitplane, index 2 the second bitplane and
+/// index 3 the pixels set on both planes. Monochrome programs only ever use indexes 0 and 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Rgba; 4],
+}
+
+const BIT_ON: Rgba = [0xF4, 0xDE, 0xCB, 0xFF];
+const BIT_ON_DARK: Rgba = [0xB0, 0x71, 0x54, 0xFF];
+const BIT_OFF: Rgba = [0x3a, 0x23, 0x17, 0xFF];
+const BIT_ON_MEDIUM: Rgba = [0xA8, 0x7E, 0x62, 0xFF];
+
+const PRESETS: [(&str, [Rgba; 4]); 5] = [
+    ("brown", [BIT_OFF, BIT_ON, BIT_ON_DARK, BIT_ON_MEDIUM]),
+    ("amber", [
+        [0x1C, 0x10, 0x00, 0xFF],
+        [0xFF, 0xB0, 0x00, 0xFF],
+        [0x99, 0x5C, 0x00, 0xFF],
+        [0xFF, 0xD8, 0x80, 0xFF],
+    ]),
+    ("green", [
+        [0x05, 0x16, 0x08, 0xFF],
+        [0x33, 0xFF, 0x66, 0xFF],
+        [0x1A, 0x80, 0x33, 0xFF],
+        [0xB0, 0xFF, 0xC8, 0xFF],
+    ]),
+    ("lcd", [
+        [0x9B, 0xBC, 0x0F, 0xFF],
+        [0x0F, 0x38, 0x0F, 0xFF],
+        [0x8B, 0xAC, 0x0F, 0xFF],
+        [0x30, 0x62, 0x30, 0xFF],
+    ]),
+    ("contrast", [
+        [0x00, 0x00, 0x00, 0xFF],
+        [0xFF, 0xFF, 0xFF, 0xFF],
+        [0xFF, 0x00, 0x00, 0xFF],
+        [0xFF, 0xFF, 0x00, 0xFF],
+    ]),
+];
+
+impl Palette {
+    /// All the built-in palettes, the default one first.
+    pub fn presets() -> Vec<Palette> {
+        PRESETS
+            .iter()
+            .map(|(name, colors)| Palette { name: name.to_string(), colors: *colors })
+            .collect()
+    }
+
+    pub fn preset(name: &str) -> Option<Palette> {
+        Self::presets().into_iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Builds a palette from a list of hex colours (`#rrggbb` or `rrggbb`).
+    /// Two colours give background and foreground, the bitplane colours are blended from them.
+    /// Four colours set all the palette entries.
+    pub fn custom(colors: &[String]) -> Result<Palette> {
+        let parsed = colors
+            .iter()
+            .map(|c| parse_hex(c))
+            .collect::<Result<Vec<_>>>()?;
+
+        let colors = match parsed[..] {
+            [off, on] => [off, on, blend(off, on), on],
+            [off, on, plane2, both] => [off, on, plane2, both],
+            _ => bail!("expected 2 or 4 colours, got {}", parsed.len()),
+        };
+
+        Ok(Palette { name: "custom".to_string(), colors })
+    }
+
+    /// Colour of a vram pixel, the pixel value being the mask of the bitplanes it is set on.
+    #[inline]
+    pub fn color(&self, pixel: u8) -> &Rgba {
+        &self.colors[usize::from(pixel & 0x3)]
+    }
+}
+
+pub fn parse_hex(color: &str) -> Result<Rgba> {
+    let hex = color.trim().trim_start_matches('#');
+
+    if hex.len() != 6 {
+        bail!("invalid colour '{}': expected 6 hex digits", color);
+    }
+
+    let value = u32::from_str_radix(hex, 16)
+        .with_context(|| format!("invalid colour '{}'", color))?;
+
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8, 0xFF])
+}
+
+fn blend(a: Rgba, b: Rgba) -> Rgba {
+    let mut out = [0xFF; 4];
+    for c in 0..3 {
+        out[c] = ((u16::from(a[c]) + u16::from(b[c])) / 2) as u8;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_accepts_hash_prefix() {
+        assert_eq!(parse_hex("#ffb000").unwrap(), [0xFF, 0xB0, 0x00, 0xFF]);
+        assert_eq!(parse_hex("102030").unwrap(), [0x10, 0x20, 0x30, 0xFF]);
+    }
+
+    #[test]
+    fn parse_hex_rejects_garbage() {
+        assert!(parse_hex("#fff").is_err());
+        assert!(parse_hex("zzzzzz").is_err());
+    }
+
+    #[test]
+    fn custom_two_colours_blends_planes() {
+        let p = Palette::custom(&["#000000".to_string(), "#ffffff".to_string()]).unwrap();
+
+        assert_eq!(p.colors[0], [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(p.colors[1], [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(p.colors[2], [0x7F, 0x7F, 0x7F, 0xFF]);
+        assert_eq!(p.colors[3], [0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn custom_rejects_wrong_count() {
+        assert!(Palette::custom(&["#000000".to_string()]).is_err());
+    }
+
+    #[test]
+    fn preset_lookup_and_default() {
+        assert_eq!(Palette::presets()[0].name, "brown");
+        assert_eq!(Palette::preset("Amber").unwrap().name, "amber");
+        assert!(Palette::preset("nope").is_none());
+    }
+
+    #[test]
+    fn color_uses_plane_mask() {
+        let p = Palette::preset("brown").unwrap();
+
+        assert_eq!(p.color(0), &BIT_OFF);
+        assert_eq!(p.color(1), &BIT_ON);
+        assert_eq!(p.color(3), &BIT_ON_MEDIUM);
+    }
+}
@@ -0,0 +1,369 @@
+//! Counts where the instructions of a running ROM go, for `--profile`.
+
+use crate::analysis::{Analysis, Decoder};
+use crate::cpu::Cpu;
+use crate::cpu_core::{MEMORY_SIZE, START_ADDRESS};
+use crate::disasm::mnemonic;
+use crate::instruction::Instruction;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Hot addresses listed in the report by default.
+pub const DEFAULT_TOP: usize = 20;
+/// Deeper calls are not followed, like the stack of the CPU.
+const MAX_CALLS: usize = 256;
+
+/// How a memory byte was used, a mask of the flags below.
+pub const EXECUTED: u8 = 1;
+pub const READ: u8 = 2;
+pub const WRITTEN: u8 = 4;
+
+/// Execution counts per address and per opcode, and the memory bytes every instruction used.
+pub struct Profiler {
+    rom: Vec<u8>,
+    total: u64,
+    executions: Vec<u64>,
+    opcodes: HashMap<u16, u64>,
+    access: Vec<u8>,
+    /// The subroutines being run, with the total when they were called.
+    calls: Vec<(u16, u64)>,
+    /// Per subroutine: how many times it was called, and the instructions run in it and in
+    /// the subroutines it called.
+    subroutines: BTreeMap<u16, (u64, u64)>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            rom: Vec::new(),
+            total: 0,
+            executions: vec![0; MEMORY_SIZE],
+            opcodes: HashMap::new(),
+            access: vec![0; MEMORY_SIZE],
+            calls: Vec::new(),
+            subroutines: BTreeMap::new(),
+        }
+    }
+
+    /// Starts over when `rom` is another ROM, counts on across resets of the same one.
+    pub fn start(&mut self, rom: &[u8]) {
+        if self.rom != rom {
+            *self = Self::new();
+            self.rom = rom.to_vec();
+        }
+        self.calls.clear();
+    }
+
+    /// Records the instruction at PC, before it runs.
+    pub fn record(&mut self, cpu: &Cpu, instruction: Instruction, opcode: u16) {
+        let pc = usize::from(cpu.pc);
+        let x = usize::from((opcode >> 8) & 0xF);
+        let n = usize::from(opcode & 0xF);
+
+        self.total += 1;
+        self.executions[pc % MEMORY_SIZE] += 1;
+        *self.opcodes.entry(opcode).or_default() += 1;
+        self.mark(pc, 2, EXECUTED);
+
+        let i = cpu.i as usize;
+        match instruction {
+            Instruction::LdB => self.mark(i, 3, WRITTEN),
+            Instruction::Store => self.mark(i, x + 1, WRITTEN),
+            Instruction::Load => self.mark(i, x + 1, READ),
+            Instruction::Drw | Instruction::MegaDrw => self.mark(i, n, READ),
+            _ => {}
+        }
+
+        if instruction == Instruction::Call {
+            let target = opcode & 0x0FFF;
+            self.subroutines.entry(target).or_default().0 += 1;
+            if self.calls.len() == MAX_CALLS {
+                self.calls.remove(0);
+            }
+            self.calls.push((target, self.total));
+        } else if instruction == Instruction::Ret
+            && let Some((target, since)) = self.calls.pop()
+        {
+            self.subroutines.entry(target).or_default().1 += self.total - since;
+        }
+    }
+
+    fn mark(&mut self, addr: usize, len: usize, flag: u8) {
+        for a in addr..addr + len {
+            self.access[a % MEMORY_SIZE] |= flag;
+        }
+    }
+
+    pub fn executions(&self, addr: usize) -> u64 {
+        self.executions[addr]
+    }
+
+    /// The `EXECUTED`, `READ` and `WRITTEN` flags of a memory byte.
+    pub fn access(&self, addr: usize) -> u8 {
+        self.access[addr]
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.total.max(1) as f64
+    }
+
+    /// Writes `profile.txt` and `profile.asm` to `dir`.
+    pub fn write(&self, dir: &Path, top: usize) -> Result<()> {
+        fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+        for (name, text) in [("profile.txt", self.report(top)), ("profile.asm", self.disassembly())] {
+            let path = dir.join(name);
+            fs::write(&path, text).with_context(|| format!("Cannot write {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// The hot addresses, the opcodes, the subroutines and the code that never ran.
+    pub fn report(&self, top: usize) -> String {
+        let mut out = format!("{} instructions run\n", self.total);
+
+        let mut hot: Vec<usize> = (0..MEMORY_SIZE).filter(|&a| self.executions[a] > 0).collect();
+        hot.sort_by_key(|&a| std::cmp::Reverse(self.executions[a]));
+        out += &format!("\nHot addresses (top {top}):\n");
+        let decoder = Decoder::new();
+        for addr in hot.into_iter().take(top) {
+            let opcode = self.word(addr);
+            let count = self.executions[addr];
+            let _ = writeln!(
+                out,
+                "  {addr:03X}  {opcode:04X}  {count:>12}  {:>5.1}%  {}",
+                self.share(count),
+                mnemonic(decoder.decode(opcode), opcode)
+            );
+        }
+
+        let mut patterns: HashMap<String, u64> = HashMap::new();
+        for (&opcode, &count) in &self.opcodes {
+            *patterns.entry(pattern(opcode)).or_default() += count;
+        }
+        let mut patterns: Vec<_> = patterns.into_iter().collect();
+        patterns.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        out += "\nOpcodes:\n";
+        for (pattern, count) in patterns {
+            let _ = writeln!(out, "  {pattern}  {count:>12}  {:>5.1}%", self.share(count));
+        }
+
+        // Subroutines still running count up to now.
+        let mut subroutines = self.subroutines.clone();
+        for &(target, since) in &self.calls {
+            subroutines.entry(target).or_default().1 += self.total - since;
+        }
+        if !subroutines.is_empty() {
+            out += "\nSubroutines (instructions including the called subroutines):\n";
+            for (addr, (calls, inclusive)) in subroutines {
+                let _ = writeln!(
+                    out,
+                    "  {addr:03X}  {calls:>10} calls  {inclusive:>12}  {:>5.1}%",
+                    self.share(inclusive)
+                );
+            }
+        }
+
+        let unexecuted = self.unexecuted();
+        if !unexecuted.is_empty() {
+            out += "\nReachable code that never ran:\n";
+            for (start, end, count) in unexecuted {
+                let _ = writeln!(out, "  {start:03X}-{end:03X}  {count} instructions");
+            }
+        }
+
+        let count = |flag| self.access.iter().filter(|&&a| a & flag != 0).count();
+        let _ = writeln!(
+            out,
+            "\nMemory: {} bytes executed, {} read, {} written",
+            count(EXECUTED),
+            count(READ),
+            count(WRITTEN)
+        );
+
+        out
+    }
+
+    /// Ranges of the code reachable from the start address that never ran, with the number of
+    /// instructions in them.
+    fn unexecuted(&self) -> Vec<(u16, u16, usize)> {
+        let mut ranges: Vec<(u16, u16, usize)> = Vec::new();
+
+        for i in Analysis::new(&self.rom).instructions.values() {
+            if self.executions[usize::from(i.addr)] > 0 {
+                continue;
+            }
+            let end = i.addr + i.len - 1;
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == i.addr => {
+                    range.1 = end;
+                    range.2 += 1;
+                }
+                _ => ranges.push((i.addr, end, 1)),
+            }
+        }
+
+        ranges
+    }
+
+    /// The ROM as code and data: every instruction with its execution count, `;` marking the
+    /// reachable ones that never ran, and the data bytes with how they were used.
+    pub fn disassembly(&self) -> String {
+        let code = Analysis::new(&self.rom).instructions;
+        let decoder = Decoder::new();
+        let end = START_ADDRESS + self.rom.len();
+        let mut out = String::new();
+        let mut addr = START_ADDRESS;
+
+        while addr < end {
+            let count = self.executions[addr];
+            let instruction = code.get(&(addr as u16));
+            if count > 0 || instruction.is_some() {
+                let opcode = self.word(addr);
+                let len = instruction.map_or(2, |i| usize::from(i.len));
+                let mark = if count > 0 { ' ' } else { ';' };
+                let text = mnemonic(instruction.map_or_else(|| decoder.decode(opcode), |i| i.instruction), opcode);
+                let _ = writeln!(out, "{mark} {addr:03X}  {opcode:04X}  {count:>12}  {text}");
+                addr += len;
+            } else {
+                let start = addr;
+                while addr < end && self.executions[addr] == 0 && !code.contains_key(&(addr as u16)) {
+                    addr += 1;
+                }
+                for chunk in (start..addr).collect::<Vec<_>>().chunks(8) {
+                    let bytes: Vec<String> = chunk.iter().map(|&a| format!("#{:02X}", self.byte(a))).collect();
+                    let used = chunk.iter().fold(0, |used, &a| used | self.access[a]);
+                    let _ = writeln!(out, "  {:03X}  DB {:<31}  ; {}", chunk[0], bytes.join(", "), usage(used));
+                }
+            }
+        }
+
+        out
+    }
+
+    fn byte(&self, addr: usize) -> u8 {
+        addr.checked_sub(START_ADDRESS).and_then(|a| self.rom.get(a)).copied().unwrap_or(0)
+    }
+
+    fn word(&self, addr: usize) -> u16 {
+        u16::from_be_bytes([self.byte(addr), self.byte(addr + 1)])
+    }
+}
+
+fn usage(access: u8) -> &'static str {
+    match (access & READ != 0, access & WRITTEN != 0) {
+        (false, false) => "unused",
+        (true, false) => "read",
+        (false, true) => "written",
+        (true, true) => "read, written",
+    }
+}
+
+/// The opcode type, e.g. `8xy4` or `Dxyn`.
+fn pattern(opcode: u16) -> String {
+    let hex = format!("{opcode:04X}");
+    match opcode >> 12 {
+        0x0 if matches!(opcode, 0x00E0 | 0x00EE | 0x00FB..=0x00FF) => hex,
+        0x0 if matches!(opcode, 0x00C0..=0x00DF) => format!("{}n", &hex[..3]),
+        0x0 => "0nnn".into(),
+        0x1 | 0x2 | 0xA | 0xB => format!("{}nnn", &hex[..1]),
+        0x3 | 0x4 | 0x6 | 0x7 | 0xC => format!("{}xkk", &hex[..1]),
+        0x5 | 0x8 | 0x9 => format!("{}xy{}", &hex[..1], &hex[3..]),
+        0xD => "Dxyn".into(),
+        _ if opcode == 0xF000 => hex,
+        _ => format!("{}x{}", &hex[..1], &hex[2..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu_core::CpuCore;
+    use crate::quirks::Platform;
+
+    fn run(opcodes: &[u16], instructions: usize) -> Profiler {
+        let rom: Vec<u8> = opcodes.iter().flat_map(|op| op.to_be_bytes()).collect();
+        let mut cpu = Cpu::new();
+        cpu.load_rom_at(&rom, START_ADDRESS).unwrap();
+        let mut core = CpuCore::new(None);
+        core.quirks = Platform::Modern.quirks();
+        let mut profiler = Profiler::new();
+        profiler.start(&rom);
+        core.profiler = Some(profiler);
+
+        for _ in 0..instructions {
+            core.tick(&mut cpu);
+        }
+        core.profiler.take().unwrap()
+    }
+
+    #[test]
+    fn counts_executions_and_memory_use() {
+        let profiler = run(
+            &[
+                0xA20C, // 200 I = 20C
+                0x6005, // 202 V0 = 5
+                0xF055, // 204 store V0 at 20C, I = 20D
+                0xD001, // 206 draw 1 byte from 20D
+                0x1208, // 208 loop
+                0x120A, // 20A never runs
+                0x0000, // 20C data
+                0x0000, // 20E
+            ],
+            10,
+        );
+
+        assert_eq!(profiler.executions(0x200), 1);
+        assert_eq!(profiler.executions(0x208), 6);
+        assert_eq!(profiler.access(0x200), EXECUTED);
+        assert_eq!(profiler.access(0x20C), WRITTEN);
+        assert_eq!(profiler.access(0x20D), READ);
+
+        let report = profiler.report(3);
+        assert!(report.contains("10 instructions run"), "{report}");
+        assert!(report.contains("208  1208             6   60.0%  JP #208"), "{report}");
+        assert!(report.contains("1nnn             6   60.0%"), "{report}");
+        assert!(report.contains("Dxyn             1"), "{report}");
+    }
+
+    #[test]
+    fn subroutines_count_inclusive_instructions() {
+        let profiler = run(
+            &[
+                0x2206, // 200 call 206
+                0x2206, // 202 call 206
+                0x1204, // 204 loop
+                0x220A, // 206 call 20A
+                0x00EE, // 208
+                0x00EE, // 20A
+            ],
+            10,
+        );
+
+        assert_eq!(profiler.subroutines[&0x206], (2, 6));
+        assert_eq!(profiler.subroutines[&0x20A], (2, 2));
+    }
+
+    #[test]
+    fn disassembly_marks_code_that_never_ran_and_data() {
+        let profiler = run(&[0x3000, 0x1200, 0x1204, 0xABCD], 4);
+
+        let asm = profiler.disassembly();
+        assert!(asm.contains("  200  3000             1  SE V0, #00"), "{asm}");
+        assert!(asm.contains("; 202  1200             0  JP #200"), "{asm}");
+        assert!(asm.contains("  204  1204             3  JP #204"), "{asm}");
+        assert!(asm.contains("  206  DB #AB, #CD"), "{asm}");
+        assert!(profiler.report(1).contains("202-203  1 instructions"));
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(pattern(0x00E0), "00E0");
+        assert_eq!(pattern(0x0123), "0nnn");
+        assert_eq!(pattern(0x8AB4), "8xy4");
+        assert_eq!(pattern(0xF355), "Fx55");
+        assert_eq!(pattern(0x2ABC), "2nnn");
+    }
+}
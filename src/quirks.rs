@@ -0,0 +1,126 @@
+use crate::chip8x;
+use crate::cpu_core::START_ADDRESS;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// The CHIP-8 interpreters whose behaviours ROMs were written against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter
+    Chip8,
+    /// What most modern interpreters do
+    #[default]
+    Modern,
+    /// SUPER-CHIP 1.1
+    Schip,
+    /// XO-CHIP
+    Xochip,
+    /// CHIP-8X, with the VIP colour and sound boards
+    Chip8x,
+    /// MegaChip, SUPER-CHIP with a true colour display and digitised sound
+    Megachip,
+}
+
+impl Platform {
+    /// The platform of a community CHIP-8 database id, e.g. "originalChip8".
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "originalChip8" | "hybridVIP" => Some(Platform::Chip8),
+            "modernChip8" => Some(Platform::Modern),
+            "chip48" | "superchip1" | "superchip" => Some(Platform::Schip),
+            "xochip" => Some(Platform::Xochip),
+            "chip8x" => Some(Platform::Chip8x),
+            _ => None,
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        let modern = Quirks::default();
+
+        match self {
+            Platform::Chip8 => Quirks { vblank: true, logic: true, ..modern },
+            Platform::Modern => modern,
+            Platform::Schip => Quirks { shift: true, memory_leave_i_unchanged: true, jump: true, ..modern },
+            Platform::Xochip => Quirks { wrap: true, ..modern },
+            Platform::Chip8x => Platform::Chip8.quirks(),
+            Platform::Megachip => Platform::Schip.quirks(),
+        }
+    }
+
+    /// Where the programs are loaded and start.
+    pub fn start_address(self) -> usize {
+        match self {
+            Platform::Chip8x => chip8x::START_ADDRESS,
+            _ => START_ADDRESS,
+        }
+    }
+}
+
+/// Behaviours that differ between interpreters, named after the community CHIP-8 database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6 / 8xyE shift Vx in place instead of setting Vx to Vy shifted.
+    pub shift: bool,
+    /// Fx55 / Fx65 add x to I instead of x + 1.
+    pub memory_increment_by_x: bool,
+    /// Fx55 / Fx65 leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the screen edges instead of being clipped.
+    pub wrap: bool,
+    /// Bnnn jumps to xnn + Vx instead of nnn + V0.
+    pub jump: bool,
+    /// Dxyn waits for the next frame.
+    pub vblank: bool,
+    /// 8xy1 / 8xy2 / 8xy3 reset VF.
+    pub logic: bool,
+    /// 8xy6 / 8xyE shift Vx in place and copy it to Vy, VF taken from the result, as this
+    /// emulator always did. Not in the database, it wins over `shift`.
+    pub legacy_shift: bool,
+}
+
+impl Quirks {
+    /// What the ROMs run with when neither the command line, the ROM file nor the ROM
+    /// database give a platform: the behaviour of this emulator before the quirks.
+    pub fn original() -> Self {
+        Quirks { legacy_shift: true, memory_leave_i_unchanged: true, ..Quirks::default() }
+    }
+
+    /// Sets a quirk by its community CHIP-8 database name, returns false for unknown names.
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            _ => return false,
+        };
+
+        *quirk = on;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_ids_map_to_platforms() {
+        assert_eq!(Platform::from_id("originalChip8"), Some(Platform::Chip8));
+        assert_eq!(Platform::from_id("superchip"), Some(Platform::Schip));
+        assert_eq!(Platform::from_id("megachip8"), None);
+    }
+
+    #[test]
+    fn set_by_database_name() {
+        let mut quirks = Platform::Modern.quirks();
+
+        assert!(quirks.set("memoryLeaveIUnchanged", true));
+        assert!(!quirks.set("bogus", true));
+        assert_eq!(quirks, Quirks { memory_leave_i_unchanged: true, ..Quirks::default() });
+    }
+}
@@ -0,0 +1,165 @@
+use crate::frame_buffer::FrameBuffer;
+use clap::ValueEnum;
+
+/// Flicker reduction applied to the RGBA frame before it is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FilterMode {
+    /// Draw the vram verbatim.
+    Off,
+    /// Phosphor persistence: erased pixels fade out over several frames.
+    Fade,
+    /// Average of the last two frames.
+    Blend,
+}
+
+impl FilterMode {
+    fn next(self) -> Self {
+        match self {
+            FilterMode::Off => FilterMode::Fade,
+            FilterMode::Fade => FilterMode::Blend,
+            FilterMode::Blend => FilterMode::Off,
+        }
+    }
+}
+
+pub struct RenderFilter {
+    mode: FilterMode,
+    decay: f32,
+    // Fade: the previous output frame. Blend: the previous unfiltered frame.
+    previous: Vec<u8>,
+}
+
+impl RenderFilter {
+    /// `decay` is the fraction of its brightness an erased pixel keeps on every frame.
+    pub fn new(mode: FilterMode, decay: f32) -> Self {
+        Self {
+            mode,
+            decay: decay.clamp(0.0, 1.0),
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> FilterMode {
+        self.mode
+    }
+
+    pub fn cycle(&mut self) {
+        self.mode = self.mode.next();
+        self.previous.clear();
+    }
+
+    /// Filters `frame`, which holds the palette colours of `vram` (one RGBA quad per pixel).
+    pub fn apply(&mut self, vram: &FrameBuffer, frame: &mut [u8]) {
+        if self.mode == FilterMode::Off {
+            return;
+        }
+
+        if self.previous.len() != frame.len() {
+            self.previous = frame.to_vec();
+            return;
+        }
+
+        match self.mode {
+            FilterMode::Off => {}
+            FilterMode::Fade => {
+                for ((out, prev), pixel) in frame
+                    .chunks_exact_mut(4)
+                    .zip(self.previous.chunks_exact(4))
+                    .zip(vram.pixels())
+                {
+                    if pixel != 0 {
+                        continue;
+                    }
+                    for c in 0..3 {
+                        let target = f32::from(out[c]);
+                        let faded = target + (f32::from(prev[c]) - target) * self.decay;
+                        out[c] = faded.round() as u8;
+                    }
+                }
+                self.previous.copy_from_slice(frame);
+            }
+            FilterMode::Blend => {
+                for (out, prev) in frame.chunks_exact_mut(4).zip(self.previous.chunks_exact_mut(4)) {
+                    for c in 0..3 {
+                        let current = out[c];
+                        out[c] = ((u16::from(current) + u16::from(prev[c])) / 2) as u8;
+                        prev[c] = current;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFF: [u8; 4] = [0, 0, 0, 0xFF];
+    const ON: [u8; 4] = [200, 100, 0, 0xFF];
+
+    fn vram(pixels: &[u8]) -> FrameBuffer {
+        let mut vram = FrameBuffer::new(pixels.len(), 1, 1);
+        for (x, &pixel) in pixels.iter().enumerate() {
+            vram.set(x, 0, pixel);
+        }
+        vram
+    }
+
+    fn frame(vram: &[u8]) -> Vec<u8> {
+        vram.iter().flat_map(|&p| if p != 0 { ON } else { OFF }).collect()
+    }
+
+    #[test]
+    fn off_leaves_frame_untouched() {
+        let mut filter = RenderFilter::new(FilterMode::Off, 0.5);
+        let mut f = frame(&[1, 0]);
+
+        filter.apply(&vram(&[1, 0]), &mut f);
+        filter.apply(&vram(&[0, 1]), &mut frame(&[0, 1]));
+
+        assert_eq!(f, frame(&[1, 0]));
+    }
+
+    #[test]
+    fn fade_decays_erased_pixels() {
+        let mut filter = RenderFilter::new(FilterMode::Fade, 0.5);
+        filter.apply(&vram(&[1, 0]), &mut frame(&[1, 0]));
+
+        let mut f = frame(&[0, 1]);
+        filter.apply(&vram(&[0, 1]), &mut f);
+        assert_eq!(&f[0..4], &[100, 50, 0, 0xFF]);
+        assert_eq!(&f[4..8], &ON);
+
+        let mut f = frame(&[0, 1]);
+        filter.apply(&vram(&[0, 1]), &mut f);
+        assert_eq!(&f[0..4], &[50, 25, 0, 0xFF]);
+    }
+
+    #[test]
+    fn blend_averages_last_two_frames() {
+        let mut filter = RenderFilter::new(FilterMode::Blend, 0.5);
+        filter.apply(&vram(&[1, 0]), &mut frame(&[1, 0]));
+
+        let mut f = frame(&[0, 0]);
+        filter.apply(&vram(&[0, 0]), &mut f);
+        assert_eq!(&f[0..4], &[100, 50, 0, 0xFF]);
+        assert_eq!(&f[4..8], &OFF);
+
+        let mut f = frame(&[0, 0]);
+        filter.apply(&vram(&[0, 0]), &mut f);
+        assert_eq!(&f[0..4], &OFF);
+    }
+
+    #[test]
+    fn cycle_goes_through_all_modes() {
+        let mut filter = RenderFilter::new(FilterMode::Off, 0.5);
+
+        filter.cycle();
+        assert_eq!(filter.mode(), FilterMode::Fade);
+        filter.cycle();
+        assert_eq!(filter.mode(), FilterMode::Blend);
+        filter.cycle();
+        assert_eq!(filter.mode(), FilterMode::Off);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::cpu::{FONT, FONT_SIZE};
+use crate::palette::Palette;
+use crate::rom_file::ROM_EXTENSIONS;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// The browser is drawn at the SCHIP hi-res resolution to fit 25 columns by 8 lines of text.
+pub const BROWSER_W: u32 = 128;
+pub const BROWSER_H: u32 = 64;
+
+/// Zip archives and Octo cartridges are listed with the ROMs.
+const CONTAINER_EXTENSIONS: [&str; 2] = ["zip", "gif"];
+
+const GLYPH_W: usize = 5;
+const LINE_H: usize = 7;
+const COLUMNS: usize = BROWSER_W as usize / GLYPH_W;
+const ROWS: usize = (BROWSER_H as usize - LINE_H) / LINE_H;
+
+/// Letters and symbols missing from the CHIP-8 font, drawn in the same 4x5 style.
+const GLYPHS: [(char, [u8; 5]); 29] = [
+    ('G', [0xF0, 0x80, 0xB0, 0x90, 0xF0]),
+    ('H', [0x90, 0x90, 0xF0, 0x90, 0x90]),
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]),
+    ('J', [0x70, 0x20, 0x20, 0xA0, 0xE0]),
+    ('K', [0x90, 0xA0, 0xC0, 0xA0, 0x90]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xF0]),
+    ('M', [0x90, 0xF0, 0xF0, 0x90, 0x90]),
+    ('N', [0x90, 0xD0, 0xB0, 0x90, 0x90]),
+    ('O', [0x60, 0x90, 0x90, 0x90, 0x60]),
+    ('P', [0xF0, 0x90, 0xF0, 0x80, 0x80]),
+    ('Q', [0x60, 0x90, 0x90, 0xB0, 0x70]),
+    ('R', [0xE0, 0x90, 0xE0, 0xA0, 0x90]),
+    ('S', [0x70, 0x80, 0x60, 0x10, 0xE0]),
+    ('T', [0xE0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0x90, 0x90, 0x90, 0x90, 0xF0]),
+    ('V', [0xA0, 0xA0, 0xA0, 0xA0, 0x40]),
+    ('W', [0x90, 0x90, 0xF0, 0xF0, 0x90]),
+    ('X', [0x90, 0x90, 0x60, 0x90, 0x90]),
+    ('Y', [0xA0, 0xA0, 0x40, 0x40, 0x40]),
+    ('Z', [0xF0, 0x10, 0x60, 0x80, 0xF0]),
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]),
+    ('-', [0x00, 0x00, 0xE0, 0x00, 0x00]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0xF0]),
+    ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    ('(', [0x20, 0x40, 0x40, 0x40, 0x20]),
+    (')', [0x40, 0x20, 0x20, 0x20, 0x40]),
+    ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    ('?', [0xE0, 0x10, 0x60, 0x00, 0x40]),
+];
+
+/// In-app ROM picker listing the ROMs of a directory.
+pub struct RomBrowser {
+    dir: PathBuf,
+    entries: Vec<PathBuf>,
+    selected: usize,
+}
+
+impl RomBrowser {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, entries: Vec::new(), selected: 0 }
+    }
+
+    /// Lists the ROMs of the directory again, sorted by name.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        self.entries = fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && is_rom(p))
+            .collect();
+        self.entries.sort();
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        Ok(())
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.entries.get(self.selected).map(PathBuf::as_path)
+    }
+
+    /// Draws the list on a BROWSER_W x BROWSER_H RGBA frame, the selected entry inverted.
+    pub fn draw(&self, frame: &mut [u8], palette: &Palette) {
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(palette.color(0));
+        }
+
+        let title = if self.entries.is_empty() { "NO ROMS" } else { "ROMS" };
+        draw_text(frame, 1, 1, title, palette, false);
+
+        let first = self.selected.saturating_sub(ROWS - 1);
+        for (row, path) in self.entries.iter().enumerate().skip(first).take(ROWS) {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let y = (row - first + 1) * LINE_H + 1;
+            draw_text(frame, 1, y, &name, palette, row == self.selected);
+        }
+    }
+}
+
+pub fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| {
+            ROM_EXTENSIONS.iter().chain(&CONTAINER_EXTENSIONS).any(|r| e.eq_ignore_ascii_case(r))
+        })
+}
+
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+
+    if let Some(digit) = c.to_digit(16) {
+        let at = digit as usize * usize::from(FONT_SIZE);
+        return FONT[at..at + 5].try_into().unwrap();
+    }
+
+    GLYPHS
+        .iter()
+        .find(|(g, _)| *g == c)
+        .or_else(|| GLYPHS.iter().find(|(g, _)| *g == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap()
+}
+
+fn draw_text(frame: &mut [u8], x: usize, y: usize, text: &str, palette: &Palette, inverted: bool) {
+    let width = BROWSER_W as usize;
+    let (fg, bg) = if inverted { (0, 1) } else { (1, 0) };
+
+    if inverted {
+        for row in y - 1..y + LINE_H - 1 {
+            for col in 0..width {
+                let i = (row * width + col) * 4;
+                frame[i..i + 4].copy_from_slice(palette.color(bg));
+            }
+        }
+    }
+
+    for (n, c) in text.chars().take(COLUMNS).enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..4 {
+                if bits & (0x80 >> col) != 0 {
+                    let i = ((y + row) * width + x + n * GLYPH_W + col) * 4;
+                    frame[i..i + 4].copy_from_slice(palette.color(fg));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_rom_checks_extension() {
+        assert!(is_rom(Path::new("games/pong.ch8")));
+        assert!(is_rom(Path::new("games/ANT.SC8")));
+        assert!(is_rom(Path::new("games/pack.zip")));
+        assert!(!is_rom(Path::new("games/readme.txt")));
+        assert!(!is_rom(Path::new("games/ch8")));
+    }
+
+    #[test]
+    fn glyph_uses_chip8_font_for_hex_digits() {
+        assert_eq!(glyph('0'), [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(glyph('b'), [0xE0, 0x90, 0xE0, 0x90, 0xE0]);
+        assert_eq!(glyph('g'), [0xF0, 0x80, 0xB0, 0x90, 0xF0]);
+        assert_eq!(glyph('~'), glyph('?'));
+    }
+
+    #[test]
+    fn refresh_lists_roms_sorted() {
+        let mut browser = RomBrowser::new(PathBuf::from("tests"));
+
+        browser.refresh().unwrap();
+        assert_eq!(browser.selected(), Some(Path::new("tests/1-chip8-logo.ch8")));
+
+        browser.up();
+        assert_eq!(browser.selected(), Some(Path::new("tests/1-chip8-logo.ch8")));
+
+        browser.down();
+        assert_eq!(browser.selected(), Some(Path::new("tests/2-ibm-logo.ch8")));
+    }
+
+    #[test]
+    fn draw_fills_the_frame() {
+        let mut browser = RomBrowser::new(PathBuf::from("tests"));
+        let palette = Palette::presets().remove(0);
+        let mut frame = vec![0; (BROWSER_W * BROWSER_H * 4) as usize];
+
+        browser.refresh().unwrap();
+        browser.draw(&mut frame, &palette);
+
+        assert!(frame.chunks_exact(4).all(|p| p == palette.color(0) || p == palette.color(1)));
+        assert!(frame.chunks_exact(4).any(|p| p == palette.color(1)));
+    }
+}
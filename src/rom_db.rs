@@ -0,0 +1,169 @@
+use crate::quirks::{Platform, Quirks};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Known ROMs shipped with the emulator, in the community CHIP-8 database format.
+const BUNDLED: &str = include_str!("../data/roms.json");
+
+/// A program of the database, with the ROMs (versions) it was released as.
+#[derive(Debug, Deserialize)]
+struct Program {
+    #[serde(default)]
+    title: String,
+    roms: HashMap<String, RomEntry>,
+}
+
+/// The settings a ROM wants, as the community CHIP-8 database describes them.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RomEntry {
+    #[serde(skip)]
+    pub title: String,
+    /// Database platform ids the ROM runs on, the preferred one first.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// Instructions per frame.
+    pub tickrate: Option<u32>,
+    /// Quirks that differ from the platform defaults, by database platform id.
+    #[serde(default)]
+    pub quirky_platforms: HashMap<String, HashMap<String, bool>>,
+}
+
+impl RomEntry {
+    /// The first platform of the list the emulator supports.
+    pub fn platform(&self) -> Option<Platform> {
+        self.platforms.iter().find_map(|id| Platform::from_id(id))
+    }
+
+    /// The quirks to run the ROM with on `platform`.
+    pub fn quirks(&self, platform: Platform) -> Quirks {
+        let mut quirks = platform.quirks();
+
+        for (id, overrides) in &self.quirky_platforms {
+            if Platform::from_id(id) == Some(platform) {
+                for (name, &on) in overrides {
+                    quirks.set(name, on);
+                }
+            }
+        }
+
+        quirks
+    }
+}
+
+/// ROM settings by SHA-1 of the ROM file.
+#[derive(Debug, Default)]
+pub struct RomDb {
+    roms: HashMap<String, RomEntry>,
+}
+
+impl RomDb {
+    /// The bundled database only.
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("Bug - the bundled ROM database is invalid")
+    }
+
+    /// The bundled database with the user file on top of it.
+    pub fn load() -> Self {
+        let mut db = Self::bundled();
+
+        if let Some(path) = Self::user_file().filter(|p| p.exists()) {
+            match Self::read(&path) {
+                Ok(user) => db.merge(user),
+                Err(e) => eprintln!("{e:#}"),
+            }
+        }
+
+        db
+    }
+
+    /// `roms.json` in the oxidate config directory, e.g. ~/.config/oxidate/roms.json.
+    pub fn user_file() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("oxidate").join("roms.json"))
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read the ROM database {}", path.display()))?;
+        Self::parse(&json).with_context(|| format!("Invalid ROM database {}", path.display()))
+    }
+
+    /// Parses a list of programs in the community CHIP-8 database format.
+    pub fn parse(json: &str) -> Result<Self> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+        let mut roms = HashMap::new();
+
+        for program in programs {
+            for (sha1, mut rom) in program.roms {
+                rom.title = program.title.clone();
+                roms.insert(sha1.to_ascii_lowercase(), rom);
+            }
+        }
+
+        Ok(Self { roms })
+    }
+
+    /// Adds the ROMs of `other`, replacing the ones already known.
+    pub fn merge(&mut self, other: RomDb) {
+        self.roms.extend(other.roms);
+    }
+
+    pub fn get(&self, sha1: &str) -> Option<&RomEntry> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: &str = r#"[{
+        "title": "My Pong",
+        "roms": {
+            "B9BBC12CEE3F7B9D3B1F69161F7D7A2D86953379": {
+                "platforms": ["megachip8", "superchip"],
+                "tickrate": 30,
+                "quirkyPlatforms": { "superchip": { "jump": false, "shift": true } }
+            }
+        }
+    }]"#;
+
+    #[test]
+    fn bundled_database_knows_the_test_roms() {
+        let db = RomDb::bundled();
+        let rom = db.get("b9bbc12cee3f7b9d3b1f69161f7d7a2d86953379").unwrap();
+
+        assert_eq!(rom.title, "IBM Logo");
+        assert_eq!(rom.platform(), Some(Platform::Chip8));
+        assert!(db.get("0000000000000000000000000000000000000000").is_none());
+    }
+
+    #[test]
+    fn user_entries_replace_the_bundled_ones() {
+        let mut db = RomDb::bundled();
+
+        db.merge(RomDb::parse(USER).unwrap());
+        let rom = db.get("b9bbc12cee3f7b9d3b1f69161f7d7a2d86953379").unwrap();
+
+        assert_eq!(rom.title, "My Pong");
+        assert_eq!(rom.tickrate, Some(30));
+        assert_eq!(rom.platform(), Some(Platform::Schip));
+    }
+
+    #[test]
+    fn quirky_platforms_override_the_platform_quirks() {
+        let db = RomDb::parse(USER).unwrap();
+        let rom = db.get("b9bbc12cee3f7b9d3b1f69161f7d7a2d86953379").unwrap();
+
+        assert_eq!(rom.quirks(Platform::Schip), Quirks { jump: false, ..Platform::Schip.quirks() });
+        assert_eq!(rom.quirks(Platform::Chip8), Platform::Chip8.quirks());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_json() {
+        assert!(RomDb::parse(r#"[{"roms": {"abc": {"tickrate": "fast"}}}]"#).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! Reads ROMs from raw files, zip archives, stdin and Octo cartridges.
+//!
+//! The format is told by the content, not by the extension: zip archives start with `PK`,
+//! Octo cartridges are GIF images and everything else is a raw ROM.
+
+use crate::quirks::Quirks;
+use serde::Deserialize;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use std::process::Command;
+use std::fs;
+
+/// Extensions of the ROMs picked in a zip archive.
+pub const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+/// Separates a zip archive from the entry to load: `games.zip#pong.ch8`.
+const ENTRY_SEPARATOR: char = '#';
+/// Assembles the Octo source of the cartridges.
+const OCTO_CLI: &str = "octo-cli";
+
+/// A ROM with the settings its file came with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomFile {
+    pub data: Vec<u8>,
+    /// Instructions per frame, from an Octo cartridge.
+    pub tickrate: Option<u32>,
+    /// From an Octo cartridge.
+    pub quirks: Option<Quirks>,
+}
+
+impl RomFile {
+    fn raw(data: Vec<u8>) -> Self {
+        Self { data, tickrate: None, quirks: None }
+    }
+}
+
+/// Reads a ROM: `-` is stdin, `archive.zip#entry` an entry of a zip archive.
+pub fn read(source: &Path) -> io::Result<RomFile> {
+    if source == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data)?;
+        return decode(data, None);
+    }
+
+    let (path, entry) = split_entry(source);
+    decode(fs::read(path)?, entry)
+}
+
+/// The file on disk a ROM source reads, to watch it.
+pub fn disk_path(source: &Path) -> &Path {
+    split_entry(source).0
+}
+
+fn split_entry(source: &Path) -> (&Path, Option<&str>) {
+    if !source.exists()
+        && let Some(s) = source.to_str()
+        && let Some((archive, entry)) = s.rsplit_once(ENTRY_SEPARATOR)
+    {
+        return (Path::new(archive), Some(entry));
+    }
+
+    (source, None)
+}
+
+fn decode(data: Vec<u8>, entry: Option<&str>) -> io::Result<RomFile> {
+    if data.starts_with(b"PK\x03\x04") {
+        read_zip(data, entry)
+    } else if entry.is_some() {
+        Err(invalid("only zip archives have entries"))
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        read_cartridge(&data)
+    } else {
+        Ok(RomFile::raw(data))
+    }
+}
+
+/// Loads the named entry, or the only ROM of the archive.
+fn read_zip(data: Vec<u8>, entry: Option<&str>) -> io::Result<RomFile> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(io::Error::other)?;
+
+    let name = match entry {
+        Some(name) => name.to_string(),
+        None => {
+            let roms: Vec<&str> = archive.file_names().filter(|n| is_rom_name(n)).collect();
+            match roms[..] {
+                [rom] => rom.to_string(),
+                [] => return Err(invalid("no ROM in the archive")),
+                _ => {
+                    let message = format!(
+                        "several ROMs in the archive, pick one with archive.zip#rom: {}",
+                        roms.join(", ")
+                    );
+                    return Err(invalid(&message));
+                }
+            }
+        }
+    };
+
+    let mut file = archive.by_name(&name).map_err(|e| invalid(&format!("{name}: {e}")))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    // An archive can hold a cartridge too.
+    decode(data, None)
+}
+
+fn is_rom_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ROM_EXTENSIONS.iter().any(|r| e.eq_ignore_ascii_case(r)))
+}
+
+/// The payload of an Octo cartridge: the program source and the emulator options.
+#[derive(Debug, Deserialize)]
+struct Cartridge {
+    program: String,
+    #[serde(default)]
+    options: OctoOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct OctoOptions {
+    tickrate: Option<u32>,
+    shift_quirks: bool,
+    load_store_quirks: bool,
+    clip_quirks: bool,
+    jump_quirks: bool,
+    v_blank_quirks: bool,
+    logic_quirks: bool,
+}
+
+impl OctoOptions {
+    fn quirks(&self) -> Quirks {
+        Quirks {
+            shift: self.shift_quirks,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            wrap: !self.clip_quirks,
+            jump: self.jump_quirks,
+            vblank: self.v_blank_quirks,
+            logic: self.logic_quirks,
+            ..Quirks::default()
+        }
+    }
+}
+
+/// Assembles the program of an Octo cartridge, its options become the ROM settings.
+fn read_cartridge(gif: &[u8]) -> io::Result<RomFile> {
+    let cartridge = cartridge_payload(gif)?;
+
+    Ok(RomFile {
+        data: assemble(&cartridge.program)?,
+        tickrate: cartridge.options.tickrate,
+        quirks: Some(cartridge.options.quirks()),
+    })
+}
+
+/// Octo hides the cartridge payload in the two low bits of the palette index of every pixel,
+/// frame after frame, four pixels per byte with the high bits first. The payload is a 32-bit
+/// big-endian length followed by that many bytes of JSON.
+fn cartridge_payload(gif: &[u8]) -> io::Result<Cartridge> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(gif).map_err(io::Error::other)?;
+
+    let mut bits = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(io::Error::other)? {
+        bits.extend(frame.buffer.iter().map(|p| p & 3));
+    }
+
+    let bytes: Vec<u8> = bits
+        .chunks_exact(4)
+        .map(|c| (c[0] << 6) | (c[1] << 4) | (c[2] << 2) | c[3])
+        .collect();
+
+    let (len, payload) = bytes.split_first_chunk::<4>().ok_or_else(|| invalid("empty cartridge"))?;
+    let payload = payload
+        .get(..u32::from_be_bytes(*len) as usize)
+        .ok_or_else(|| invalid("truncated cartridge"))?;
+    serde_json::from_slice(payload).map_err(|e| invalid(&format!("not an Octo cartridge: {e}")))
+}
+
+/// Cartridges carry the Octo source, octo-cli turns it into a ROM.
+fn assemble(source: &str) -> io::Result<Vec<u8>> {
+    // Removed when dropped, whatever happens.
+    let dir = tempfile::Builder::new().prefix("oxidate-cartridge-").tempdir()?;
+    let input = dir.path().join("cartridge.8o");
+    let output = dir.path().join("cartridge.ch8");
+    fs::write(&input, source)?;
+
+    let result = Command::new(OCTO_CLI).arg(&input).arg(&output).output();
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(invalid(&format!(
+            "Octo cartridges hold the program source, install {OCTO_CLI} to assemble it"
+        ))),
+        Err(e) => Err(e),
+        Ok(out) if !out.status.success() => {
+            let message = String::from_utf8_lossy(&out.stdout) + String::from_utf8_lossy(&out.stderr);
+            Err(invalid(&format!("{OCTO_CLI} failed: {}", message.trim())))
+        }
+        Ok(_) => fs::read(&output),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Hides `payload` in a 32x8 GIF the way Octo does.
+    fn cartridge(payload: &str) -> Vec<u8> {
+        let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(payload.as_bytes());
+
+        let mut pixels: Vec<u8> = bytes
+            .iter()
+            .flat_map(|b| [b >> 6, (b >> 4) & 3, (b >> 2) & 3, b & 3])
+            .map(|bits| 4 | bits)
+            .collect();
+        pixels.resize(pixels.len().div_ceil(256) * 256, 4);
+
+        let palette = [0u8; 8 * 3];
+        let mut gif = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut gif, 32, 8, &palette).unwrap();
+        for frame in pixels.chunks(256) {
+            let frame = gif::Frame { width: 32, height: 8, buffer: Cow::Borrowed(frame), ..Default::default() };
+            encoder.write_frame(&frame).unwrap();
+        }
+        drop(encoder);
+        gif
+    }
+
+    #[test]
+    fn raw_files_are_read_as_is() {
+        let rom = decode(vec![0x00, 0xE0], None).unwrap();
+        assert_eq!(rom, RomFile::raw(vec![0x00, 0xE0]));
+    }
+
+    #[test]
+    fn zip_archives_load_their_only_rom() {
+        let archive = zip(&[("readme.txt", b"hello"), ("games/pong.ch8", &[0x12, 0x00])]);
+
+        assert_eq!(decode(archive, None).unwrap().data, [0x12, 0x00]);
+    }
+
+    #[test]
+    fn zip_archives_with_several_roms_need_an_entry() {
+        let archive = zip(&[("a.ch8", &[0xA1]), ("b.sc8", &[0xB1])]);
+
+        let error = decode(archive.clone(), None).unwrap_err();
+        assert!(error.to_string().contains("a.ch8, b.sc8"), "{error}");
+        assert_eq!(decode(archive.clone(), Some("b.sc8")).unwrap().data, [0xB1]);
+        assert!(decode(archive, Some("c.ch8")).is_err());
+    }
+
+    #[test]
+    fn split_entry_only_when_the_path_does_not_exist() {
+        assert_eq!(split_entry(Path::new("roms.zip#pong.ch8")), (Path::new("roms.zip"), Some("pong.ch8")));
+        assert_eq!(split_entry(Path::new("tests/2-ibm-logo.ch8")), (Path::new("tests/2-ibm-logo.ch8"), None));
+    }
+
+    #[test]
+    fn cartridge_payload_and_options_are_decoded() {
+        let payload = r#"{"program": ": main loop again", "options": {"tickrate": 20, "shiftQuirks": true, "clipQuirks": true}}"#;
+        let gif = cartridge(payload);
+        assert!(gif.starts_with(b"GIF89a"));
+
+        let cartridge = cartridge_payload(&gif).unwrap();
+        assert_eq!(cartridge.program, ": main loop again");
+        assert_eq!(cartridge.options.tickrate, Some(20));
+        assert_eq!(cartridge.options.quirks(), Quirks { shift: true, ..Quirks::default() });
+    }
+
+    #[test]
+    fn options_map_to_quirks() {
+        let options: OctoOptions =
+            serde_json::from_str(r#"{"tickrate": 7, "loadStoreQuirks": true, "vBlankQuirks": true}"#).unwrap();
+
+        assert_eq!(options.tickrate, Some(7));
+        assert_eq!(
+            options.quirks(),
+            Quirks { memory_leave_i_unchanged: true, wrap: true, vblank: true, ..Quirks::default() }
+        );
+    }
+
+    #[test]
+    fn not_a_cartridge() {
+        let error = cartridge_payload(&cartridge("not json")).unwrap_err();
+        assert!(error.to_string().contains("not an Octo cartridge"));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::audio::{PITCH_RANGE, VOLUME_RANGE};
+use crate::keypad::Keymap;
+use crate::palette::Palette;
+use crate::quirks::Platform;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Global settings of the config file. Every one is optional, the command line wins over them.
+///
+/// ```toml
+/// speed = 15
+/// platform = "schip"
+/// palette = "amber"
+/// scale = 12
+/// keys = "x123qweasdzc4rfv"
+/// keys2 = "m678yuihjkn,9ol."
+/// rom-dir = "~/roms"
+///
+/// [audio]
+/// pitch = 440.0
+/// volume = 0.2
+///
+/// [vip]
+/// monitor = "~/vip/monitor.bin"
+/// interpreter = "~/vip/chip8.bin"
+/// ```
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    pub speed: Option<u32>,
+    pub platform: Option<Platform>,
+    pub palette: Option<String>,
+    pub colors: Option<Vec<String>>,
+    pub scale: Option<u32>,
+    pub keys: Option<String>,
+    pub keys2: Option<String>,
+    pub rom_dir: Option<PathBuf>,
+    pub audio: AudioSettings,
+    pub vip: VipSettings,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioSettings {
+    /// Buzzer pitch in Hz.
+    pub pitch: Option<f32>,
+    /// Buzzer volume, 0.0 - 1.0.
+    pub volume: Option<f32>,
+}
+
+/// The firmware dumps `--vip` needs.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VipSettings {
+    /// The 512 bytes of the monitor ROM.
+    pub monitor: Option<PathBuf>,
+    /// The CHIP-8 interpreter, the first 512 bytes of the RAM.
+    pub interpreter: Option<PathBuf>,
+}
+
+impl Settings {
+    /// `config.toml` in the oxidate config directory, e.g. ~/.config/oxidate/config.toml.
+    pub fn default_file() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("oxidate").join("config.toml"))
+    }
+
+    /// Reads `path`, or the default file when there is one.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_file().filter(|p| p.exists()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Cannot read the config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut settings: Self = toml::from_str(text)?;
+        settings.validate()?;
+
+        for path in [&mut settings.rom_dir, &mut settings.vip.monitor, &mut settings.vip.interpreter] {
+            if let Some(dir) = path
+                && let Ok(rest) = dir.strip_prefix("~")
+                && let Some(home) = dirs::home_dir()
+            {
+                *path = Some(home.join(rest));
+            }
+        }
+
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.speed == Some(0) {
+            bail!("`speed` must be at least 1");
+        }
+        if let Some(scale) = self.scale
+            && !(1..=64).contains(&scale)
+        {
+            bail!("`scale` must be between 1 and 64, not {scale}");
+        }
+        if let Some(palette) = &self.palette
+            && Palette::preset(palette).is_none()
+        {
+            bail!("`palette` {palette:?} is unknown, use brown, amber, green, lcd or contrast");
+        }
+        if let Some(colors) = &self.colors {
+            Palette::custom(colors).context("`colors` is invalid")?;
+        }
+        if let Some(keys) = &self.keys
+            && Keymap::from_layout(keys).is_none()
+        {
+            bail!("`keys` must list 16 keys, the ones of the CHIP-8 keys 0 to F");
+        }
+        if let Some(keys) = &self.keys2
+            && Keymap::from_layout(keys).is_none()
+        {
+            bail!("`keys2` must list 16 keys, the ones of the CHIP-8X keys 0 to F");
+        }
+        if let Some(pitch) = self.audio.pitch
+            && !PITCH_RANGE.contains(&pitch)
+        {
+            bail!("`audio.pitch` must be between 20 and 20000 Hz, not {pitch}");
+        }
+        if let Some(volume) = self.audio.volume
+            && !VOLUME_RANGE.contains(&volume)
+        {
+            bail!("`audio.volume` must be between 0.0 and 1.0, not {volume}");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_every_setting() {
+        let settings = Settings::parse(
+            r#"
+            speed = 15
+            platform = "schip"
+            palette = "amber"
+            scale = 12
+            keys = "0123456789abcdef"
+            rom-dir = "/roms"
+
+            [audio]
+            pitch = 880.0
+            volume = 0.5
+
+            [vip]
+            monitor = "/vip/monitor.bin"
+            interpreter = "/vip/chip8.bin"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.speed, Some(15));
+        assert_eq!(settings.platform, Some(Platform::Schip));
+        assert_eq!(settings.palette.as_deref(), Some("amber"));
+        assert_eq!(settings.scale, Some(12));
+        assert_eq!(settings.rom_dir, Some(PathBuf::from("/roms")));
+        assert_eq!(settings.audio.pitch, Some(880.0));
+        assert_eq!(settings.audio.volume, Some(0.5));
+        assert_eq!(settings.vip.monitor, Some(PathBuf::from("/vip/monitor.bin")));
+        assert_eq!(settings.vip.interpreter, Some(PathBuf::from("/vip/chip8.bin")));
+    }
+
+    #[test]
+    fn empty_file_sets_nothing() {
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn errors_name_the_bad_key() {
+        let error = |text| format!("{:#}", Settings::parse(text).unwrap_err());
+
+        assert!(error("sped = 15").contains("sped"));
+        assert!(error("speed = \"fast\"").contains("speed"));
+        assert!(error("platform = \"gameboy\"").contains("gameboy"));
+        assert!(error("scale = 100").contains("`scale`"));
+        assert!(error("palette = \"pink\"").contains("`palette`"));
+        assert!(error("keys = \"abc\"").contains("`keys`"));
+        assert!(error("keys2 = \"abc\"").contains("`keys2`"));
+        assert!(error("[audio]\nvolume = 2.0").contains("`audio.volume`"));
+        assert!(error("[audio]\nbass = 1").contains("bass"));
+    }
+}
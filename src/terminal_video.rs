@@ -0,0 +1,228 @@
+use crate::clock::Sync;
+use crate::config::{H, W};
+use crate::control::{Command, Status};
+use crate::emulator::{faster, slower};
+use crate::frame_buffer::FrameBuffer;
+use crate::keypad::Keymap;
+use crate::palette::{Palette, Rgba};
+use anyhow::Result;
+use crossterm::cursor::{Hide, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use ratatui::prelude::*;
+use std::io::{stdout, Stdout};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Terminals without the kitty keyboard protocol only report key presses (and auto-repeats),
+/// so a key is considered released when nothing has been heard of it for this long.
+const KEY_HOLD: Duration = Duration::from_millis(150);
+const FRAME: Duration = Duration::from_millis(16);
+
+/// Game screen drawn in the terminal with half-block characters: every cell shows two
+/// vertically stacked pixels, so 64x32 fits in 64x16 cells.
+pub struct TerminalVideo {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    vram: FrameBuffer,
+    status: Receiver<Status>,
+    commands: Sender<Command>,
+    keymap: Keymap,
+    palettes: Vec<Palette>,
+    palette_idx: usize,
+    pressed: [Option<Instant>; 16],
+    key_release_events: bool,
+    message: Option<String>,
+    paused: bool,
+    speed: u32,
+}
+
+impl TerminalVideo {
+    pub fn new(
+        status: Receiver<Status>,
+        commands: Sender<Command>,
+        keymap: Keymap,
+        palettes: Vec<Palette>,
+        speed: u32,
+    ) -> Result<Self> {
+        enable_raw_mode()?;
+
+        let mut stdout = stdout();
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+
+        let key_release_events = supports_keyboard_enhancement().unwrap_or(false);
+        if key_release_events {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+        Ok(Self {
+            terminal,
+            vram: FrameBuffer::new(W, H, 1),
+            status,
+            commands,
+            keymap,
+            palettes,
+            palette_idx: 0,
+            pressed: [None; 16],
+            key_release_events,
+            message: None,
+            paused: false,
+            speed,
+        })
+    }
+
+    /// Draws the screen and reads the keyboard until Esc or Ctrl-C is pressed.
+    pub fn run(&mut self) -> Result<()> {
+        loop {
+            while let Ok(status) = self.status.try_recv() {
+                match status {
+                    Status::Frame(vram) => self.vram = vram,
+                    Status::Error(e) => self.message = Some(e),
+                    Status::Halted => self.message = Some("Halted".to_string()),
+                    Status::Speed(ipf) => self.speed = ipf,
+                    // The terminal keeps the palette colours and shows no MegaChip display.
+                    Status::Resolution(..) | Status::Colours(_) | Status::TrueColour(_) => {}
+                }
+            }
+
+            self.draw()?;
+            let _ = self.commands.send(Command::Tick(Sync::Vsync));
+
+            let deadline = Instant::now() + FRAME;
+            while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+                if !event::poll(timeout)? {
+                    break;
+                }
+                if let Event::Key(k) = event::read()?
+                    && !self.key_event(k)
+                {
+                    let _ = self.commands.send(Command::Quit);
+                    return Ok(());
+                }
+            }
+
+            if !self.key_release_events {
+                self.release_stale_keys();
+            }
+        }
+    }
+
+    pub fn quit(&mut self) -> Result<()> {
+        if self.key_release_events {
+            execute!(stdout(), PopKeyboardEnhancementFlags)?;
+        }
+        disable_raw_mode()?;
+        execute!(stdout(), LeaveAlternateScreen, Show)?;
+        Ok(())
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let vram = &self.vram;
+        let palette = &self.palettes[self.palette_idx];
+        let message = if self.paused { Some("Paused") } else { self.message.as_deref() };
+
+        self.terminal.draw(|f| {
+            let area = f.area();
+            let width = (vram.width() as u16).min(area.width);
+            let height = ((vram.height() / 2) as u16).min(area.height);
+            let left = area.x + (area.width - width) / 2;
+            let top = area.y + (area.height - height) / 2;
+            let buf = f.buffer_mut();
+
+            for row in 0..height {
+                for col in 0..width {
+                    let (x, y) = (usize::from(col), usize::from(row) * 2);
+                    let (upper, lower) = (vram.get(x, y), vram.get(x, y + 1));
+
+                    buf[(left + col, top + row)]
+                        .set_char('▀')
+                        .set_fg(color(palette.color(upper)))
+                        .set_bg(color(palette.color(lower)));
+                }
+            }
+
+            if let Some(message) = message {
+                let line = Rect { y: area.bottom() - 1, height: 1, ..area };
+                f.render_widget(Line::from(message).centered(), line);
+            }
+        })?;
+
+        Ok(())
+    }
+
+    /// Returns false when the frontend has to quit.
+    fn key_event(&mut self, k: KeyEvent) -> bool {
+        match k.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::F(2) if k.kind == KeyEventKind::Press => {
+                self.palette_idx = (self.palette_idx + 1) % self.palettes.len();
+            }
+            KeyCode::F(5) if k.kind == KeyEventKind::Press => {
+                self.message = None;
+                let _ = self.commands.send(Command::Reset);
+            }
+            KeyCode::F(6) | KeyCode::Pause if k.kind == KeyEventKind::Press => {
+                self.paused = !self.paused;
+                let cmd = if self.paused { Command::Pause } else { Command::Resume };
+                let _ = self.commands.send(cmd);
+            }
+            KeyCode::F(7) if k.kind == KeyEventKind::Press && self.paused => {
+                let _ = self.commands.send(Command::Step);
+            }
+            KeyCode::Char('-' | '=' | '+') if k.kind != KeyEventKind::Release => {
+                self.speed = if k.code == KeyCode::Char('-') {
+                    slower(self.speed)
+                } else {
+                    faster(self.speed)
+                };
+                self.message = Some(format!("{} ipf", self.speed));
+                let _ = self.commands.send(Command::SetSpeed(self.speed));
+            }
+            KeyCode::Char(c) => {
+                if let Some(key) = self.keymap.key(c) {
+                    let pressed = k.kind != KeyEventKind::Release;
+                    self.set_key(key, pressed);
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        let was_pressed = self.pressed[usize::from(key)].is_some();
+
+        self.pressed[usize::from(key)] = if pressed { Some(Instant::now()) } else { None };
+
+        if was_pressed != pressed {
+            let _ = self.commands.send(Command::Key(key, pressed));
+        }
+    }
+
+    fn release_stale_keys(&mut self) {
+        for key in 0..16u8 {
+            if let Some(at) = self.pressed[usize::from(key)]
+                && at.elapsed() >= KEY_HOLD
+            {
+                self.set_key(key, false);
+            }
+        }
+    }
+}
+
+fn color(rgba: &Rgba) -> Color {
+    Color::Rgb(rgba[0], rgba[1], rgba[2])
+}
@@ -0,0 +1,238 @@
+//! The basic-block translator, what the emulator runs the CHIP-8 code with.
+//!
+//! The straight-line code from an address, up to the first instruction that jumps, skips,
+//! waits, draws or writes memory, is decoded once into a block: the op_ functions to call and
+//! their opcodes, run one after the other without fetching, decoding or moving the program
+//! counter. Writing memory drops the blocks over it, see Cpu::invalidate. The code written
+//! over again and again, self-modifying, is left to CpuCore::tick.
+
+use crate::cpu::Cpu;
+use crate::cpu_core::{CpuCore, Handler, MEMORY_SIZE};
+use crate::instruction::Instruction;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// The longest block, in instructions.
+const MAX_LEN: usize = 64;
+/// Blocks written over this many times are not translated again.
+const SELF_MODIFYING: u8 = 4;
+
+/// Straight-line code: every instruction but the last one runs on to the next.
+pub struct Block {
+    ops: Box<[(Handler, u16)]>,
+}
+
+impl Block {
+    /// The instructions of the block.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+}
+
+/// The blocks translated, by the address they start at.
+pub struct Blocks {
+    blocks: Vec<Option<Rc<Block>>>,
+    /// How many times the block starting at every address was written over.
+    rewritten: Vec<u8>,
+}
+
+impl Blocks {
+    pub fn new() -> Self {
+        Self { blocks: vec![None; MEMORY_SIZE], rewritten: vec![0; MEMORY_SIZE] }
+    }
+
+    /// Drops the blocks over the bytes `addrs` that were written.
+    pub fn invalidate(&mut self, addrs: Range<usize>) {
+        let end = addrs.end.min(MEMORY_SIZE);
+        for start in addrs.start.saturating_sub(2 * MAX_LEN - 1).min(end)..end {
+            if let Some(block) = &self.blocks[start]
+                && start + 2 * block.len() > addrs.start
+            {
+                self.blocks[start] = None;
+                self.rewritten[start] = self.rewritten[start].saturating_add(1);
+            }
+        }
+    }
+
+    /// Drops every block, when the memory or the mode changes all at once.
+    pub fn clear(&mut self) {
+        self.blocks.fill(None);
+    }
+}
+
+/// The instructions a block ends with: the next one is not the one after them, or has to be
+/// decoded again.
+fn ends_block(instruction: Instruction) -> bool {
+    use Instruction::*;
+
+    matches!(
+        instruction,
+        Sys | MachineRet | Ret | Jp | Call | SeByte | SneByte | SeReg | SneReg | JpV0 | Drw
+            | Skp | Sknp | LdVxK | LdB | Store | Skp2 | Sknp2 | MegaMode | LdILong | MegaDrw
+            | Exit | Low | High | LdIWord | Unknown
+    )
+}
+
+impl CpuCore {
+    /// Runs the block at the program counter, no more than `budget` instructions. Returns the
+    /// number of instructions run. The state is the one of as many ticks.
+    pub fn run_block(&mut self, cpu: &mut Cpu, budget: u32) -> u32 {
+        let pc = usize::from(cpu.pc);
+        // The profiler counts the instructions one at a time.
+        if self.profiler.is_some() || cpu.blocks.rewritten[pc] >= SELF_MODIFYING {
+            self.tick(cpu);
+            return 1;
+        }
+
+        let block = match &cpu.blocks.blocks[pc] {
+            Some(block) => Rc::clone(block),
+            None => {
+                let block = Rc::new(self.translate(cpu));
+                cpu.blocks.blocks[pc] = Some(Rc::clone(&block));
+                block
+            }
+        };
+        if block.len() > budget as usize {
+            self.tick(cpu);
+            return 1;
+        }
+
+        let (&(last, opcode), body) = block.ops.split_last().expect("Bug - empty block");
+        for &(op, opcode) in body {
+            op(self, cpu, opcode);
+        }
+
+        // The last instruction runs as tick runs it.
+        cpu.pc = (pc + 2 * body.len()) as u16;
+        cpu.draw_flag = false;
+        last(self, cpu, opcode);
+        if !cpu.wait_for_key {
+            cpu.pc = (cpu.pc + 2) % MEMORY_SIZE as u16;
+        }
+        block.len() as u32
+    }
+
+    fn translate(&self, cpu: &Cpu) -> Block {
+        let mut ops = Vec::new();
+        let mut addr = usize::from(cpu.pc);
+
+        loop {
+            let opcode = u16::from_be_bytes([cpu.mem[addr], cpu.mem[addr + 1]]);
+            let instruction = self.decode(cpu, opcode);
+            ops.push((Self::handler(instruction), opcode));
+            addr += 2;
+
+            // The program counter wraps after the last word.
+            if ends_block(instruction) || ops.len() == MAX_LEN || addr >= MEMORY_SIZE - 1 {
+                return Block { ops: ops.into_boxed_slice() };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SCHIP_W, W};
+    use crate::quirks::Platform;
+
+    fn cpu(rom: &[u8], platform: Platform) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_at(rom, platform.start_address()).unwrap();
+        cpu
+    }
+
+    /// Runs the ROM by blocks and by ticks side by side, the same state after every block.
+    fn differential(rom: &[u8], platform: Platform, instructions: u32) {
+        let (mut blocks, mut ticks) = (cpu(rom, platform), cpu(rom, platform));
+        let (mut core, mut interpreter) = (CpuCore::new(None), CpuCore::new(None));
+        (core.platform, core.quirks) = (platform, platform.quirks());
+        (interpreter.platform, interpreter.quirks) = (platform, platform.quirks());
+
+        let mut ran = 0;
+        while ran < instructions && !blocks.panic {
+            let pc = blocks.pc;
+            let n = core.run_block(&mut blocks, 16);
+            for _ in 0..n {
+                interpreter.tick(&mut ticks);
+            }
+            ran += n;
+
+            let state = |cpu: &Cpu| {
+                (cpu.pc, cpu.i, cpu.v, cpu.sp, cpu.stack, cpu.delay_timer, cpu.sound_timer)
+            };
+            let at = format!("the block of {n} at {pc:03X}");
+            assert_eq!(state(&blocks), state(&ticks), "{at}");
+            assert_eq!((blocks.draw_flag, blocks.wait_for_key), (ticks.draw_flag, ticks.wait_for_key), "{at}");
+            assert_eq!(core.wait_vblank, interpreter.wait_vblank, "{at}");
+            assert!(blocks.mem == ticks.mem && blocks.vram == ticks.vram, "{at}");
+            assert_eq!(blocks.colours, ticks.colours, "{at}");
+            (core.wait_vblank, interpreter.wait_vblank) = (false, false);
+        }
+    }
+
+    #[test]
+    fn blocks_run_as_the_interpreter() {
+        for rom in ["1-chip8-logo", "2-ibm-logo", "3-corax+", "4-flags", "8-scrolling"] {
+            let rom = std::fs::read(format!("tests/{rom}.ch8")).unwrap();
+            for platform in [Platform::Chip8, Platform::Modern, Platform::Schip] {
+                differential(&rom, platform, 5_000);
+            }
+        }
+    }
+
+    #[test]
+    fn self_modifying_code_runs_as_the_interpreter() {
+        let rom = [
+            0xA2, 0x0A, // 200 LD I, #20A
+            0x60, 0x70, // 202 LD V0, #70
+            0x61, 0x01, // 204 LD V1, #01
+            0xF1, 0x55, // 206 LD [I], V1      writes ADD V0, #01 at 20A
+            0x72, 0x00, // 208 ADD V2, #00
+            0x72, 0x02, // 20A ADD V2, #02
+            0x12, 0x00, // 20C JP #200
+        ];
+        differential(&rom, Platform::Chip8, 1_000);
+
+        let mut cpu = cpu(&rom, Platform::Chip8);
+        let mut core = CpuCore::new(None);
+        for _ in 0..20 {
+            core.run_block(&mut cpu, 16);
+        }
+        assert!(cpu.blocks.rewritten[0x208] >= SELF_MODIFYING);
+        assert!(cpu.blocks.blocks[0x208].is_none());
+    }
+
+    #[test]
+    fn blocks_end_on_jumps_and_the_budget() {
+        // LD V0, #01 / ADD V0, #01 / JP #200
+        let mut cpu = cpu(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x00], Platform::Chip8);
+        let mut core = CpuCore::new(None);
+
+        assert_eq!(core.run_block(&mut cpu, 16), 3);
+        assert_eq!((cpu.pc, cpu.v[0]), (0x200, 2));
+        assert_eq!(core.run_block(&mut cpu, 2), 1);
+        assert_eq!((cpu.pc, cpu.v[0]), (0x202, 1));
+    }
+
+    #[test]
+    fn blocks_end_on_the_superchip_mode_switches() {
+        let rom = [
+            0x00, 0xFF, // 200 HIGH
+            0x60, 0x01, // 202 LD V0, #01
+            0x00, 0xFE, // 204 LOW
+            0x61, 0x02, // 206 LD V1, #02
+            0x00, 0xFD, // 208 EXIT
+        ];
+        let mut cpu = cpu(&rom, Platform::Schip);
+        let mut core = CpuCore::new(None);
+        core.platform = Platform::Schip;
+
+        assert_eq!(core.run_block(&mut cpu, 16), 1);
+        assert_eq!(cpu.vram.width(), SCHIP_W);
+        assert_eq!(core.run_block(&mut cpu, 16), 2);
+        assert_eq!(cpu.vram.width(), W);
+        assert_eq!(core.run_block(&mut cpu, 16), 2);
+        assert_eq!((cpu.pc, cpu.v[0], cpu.v[1], cpu.running), (0x20A, 1, 2, false));
+    }
+}
@@ -1,11 +1,25 @@
-use crate::config::{H, W, WXH};
-use pixels::{Pixels, SurfaceTexture};
-use std::sync::mpsc::Receiver;
+use crate::chip8x::ColourBoard;
+use crate::clock::Sync;
+use crate::config::{H, W};
+use crate::control::{Command, Status};
+use crate::emulator::{faster, slower};
+use crate::frame_buffer::FrameBuffer;
+use crate::keypad::Keymap;
+use crate::palette::Palette;
+use crate::render_filter::{FilterMode, RenderFilter};
+use crate::rom_browser::{RomBrowser, BROWSER_H, BROWSER_W};
+use clap::ValueEnum;
+use pixels::{wgpu, Pixels, PixelsBuilder, SurfaceTexture};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    dpi::LogicalSize,
+    event::{ElementState, KeyEvent, WindowEvent},
     event_loop::{ActiveEventLoop /*ControlFlow,*/},
-    window::Window,
+    keyboard::{KeyCode, PhysicalKey},
+    window::{Fullscreen, Window},
 };
 
 const WIDTH: u32 = W as u32;
@@ -15,49 +29,121 @@ type _Error = Box<dyn std::error::Error>;
 type _Result<T> = Result<T, _Error>;
 
 pub struct VideoInput {
-    window: Option<&'static Window>,
+    window: Option<Arc<Window>>,
     pixels: Option<Pixels<'static>>,
-    vram: [u8; WXH],
-    rx: Option<Receiver<[u8; WXH]>>,
+    vram: FrameBuffer,
+    /// The MegaChip display, RGBA, shown instead of the vram in the MegaChip mode.
+    true_colour: Option<Box<[u8]>>,
+    /// The CHIP-8X colours, shown instead of the palette ones.
+    colours: Option<ColourBoard>,
+    status: Receiver<Status>,
+    commands: Sender<Command>,
+    keymap: Keymap,
+    /// The second keypad of CHIP-8X.
+    keymap2: Keymap,
+    palettes: Vec<Palette>,
+    palette_idx: usize,
+    filter: RenderFilter,
+    scale: u32,
+    buffer_size: (u32, u32),
+    /// The emulated display size.
+    resolution: (u32, u32),
+    paused: bool,
+    halted: bool,
+    speed: u32,
+    turbo: bool,
+    browser: RomBrowser,
+    browsing: bool,
 }
 
 impl VideoInput {
-    pub fn new(rx: Receiver<[u8; WXH]>) -> Self {
+    /// `palettes` is the list the palette hotkey cycles through, starting from the first one.
+    /// `scale` is the initial window size as a multiple of the emulated resolution.
+    /// `speed` is the emulation speed the CPU thread starts with, in instructions per frame.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        status: Receiver<Status>,
+        commands: Sender<Command>,
+        keymap: Keymap,
+        keymap2: Keymap,
+        palettes: Vec<Palette>,
+        filter: RenderFilter,
+        scale: u32,
+        speed: u32,
+        browser: RomBrowser,
+    ) -> Self {
+        assert!(!palettes.is_empty(), "Bug - at least one palette is needed");
+
         Self {
             window: None,
             pixels: None,
-            vram: [0; WXH],
-            rx: Some(rx),
+            vram: FrameBuffer::new(W, H, 1),
+            true_colour: None,
+            colours: None,
+            status,
+            commands,
+            keymap,
+            keymap2,
+            palettes,
+            palette_idx: 0,
+            filter,
+            scale: scale.max(1),
+            buffer_size: (WIDTH, HEIGHT),
+            resolution: (WIDTH, HEIGHT),
+            paused: false,
+            halted: false,
+            speed,
+            turbo: false,
+            browser,
+            browsing: false,
+        }
+    }
+
+    /// Shows the ROM browser, listing the ROM directory again.
+    pub fn open_browser(&mut self) {
+        if let Err(e) = self.browser.refresh() {
+            eprintln!("Cannot list the ROMs: {e}");
         }
+        self.browsing = true;
     }
 }
-const BIT_ON: [u8; 4] = [0xF4, 0xDE, 0xCB, 0xFF];
-const _BIT_ON_DARK: [u8; 4] = [0xB0, 0x71, 0x54, 0xFF];
-const BIT_OFF: [u8; 4] = [0x3a, 0x23, 0x17, 0xFF];
-const _BIT_OFF_DARK: [u8; 4] = [0x31, 0x1f, 0x13, 0xFF];
-const _BIT_ON_MEDIUM: [u8; 4] = [0xA8, 0x7E, 0x62, 0xFF];
+
+const KEY_BROWSER: KeyCode = KeyCode::F1;
+const KEY_CYCLE_PALETTE: KeyCode = KeyCode::F2;
+const KEY_CYCLE_FILTER: KeyCode = KeyCode::F3;
+const KEY_RESET: KeyCode = KeyCode::F5;
+const KEY_PAUSE: KeyCode = KeyCode::F6;
+const KEY_FRAME_ADVANCE: KeyCode = KeyCode::F7;
+const KEY_SLOWER: KeyCode = KeyCode::Minus;
+const KEY_FASTER: KeyCode = KeyCode::Equal;
+const KEY_TURBO: KeyCode = KeyCode::Tab;
+const KEY_FULLSCREEN: KeyCode = KeyCode::F11;
 
 impl ApplicationHandler for VideoInput {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let (width, height) = self.buffer_size;
         let window = event_loop
             .create_window(
                 Window::default_attributes()
-                    .with_title("0xID8")
-                    .with_resizable(false)
-                    .with_inner_size(winit::dpi::LogicalSize::new(640.0, 320.0)),
+                    .with_title(self.title())
+                    .with_resizable(true)
+                    .with_min_inner_size(LogicalSize::new(width, height))
+                    .with_inner_size(LogicalSize::new(width * self.scale, height * self.scale)),
             )
             .unwrap();
 
+        let window = Arc::new(window);
         let size = window.inner_size();
-        let window_ref: &'static Window = Box::leak(Box::new(window));
-        let surface = SurfaceTexture::new(size.width, size.height, window_ref);
-        let pixels = Pixels::new(WIDTH, HEIGHT, surface).unwrap();
+        let surface = SurfaceTexture::new(size.width, size.height, Arc::clone(&window));
+        // The default renderer already scales by integer factors and centers the image,
+        // the clear colour fills the letterbox around it.
+        let pixels = PixelsBuilder::new(width, height, surface)
+            .clear_color(clear_color(&self.palettes[self.palette_idx]))
+            .build()
+            .unwrap();
 
-        self.window = Some(window_ref);
+        self.window = Some(window);
         self.pixels = Some(pixels);
-
-        let scale_factor = window_ref.scale_factor();
-        println!("Scale factor : {}", scale_factor);
     }
 
     fn window_event(
@@ -68,44 +154,294 @@ impl ApplicationHandler for VideoInput {
     ) {
         match event {
             WindowEvent::CloseRequested => {
+                self.send(Command::Quit);
                 event_loop.exit();
             }
 
+            WindowEvent::Resized(size) => {
+                if let Some(pixels) = &mut self.pixels
+                    && let Err(e) = pixels.resize_surface(size.width, size.height)
+                {
+                    eprintln!("Cannot resize the surface to {}x{}: {e}", size.width, size.height);
+                }
+            }
+
             WindowEvent::RedrawRequested => {
                 self.redraw_requested();
             }
 
+            WindowEvent::KeyboardInput { event, .. } => {
+                if self.browsing {
+                    self.browser_input(event);
+                } else {
+                    self.keyboard_input(event);
+                }
+            }
+
+            WindowEvent::DroppedFile(path) => {
+                self.load_rom(path);
+            }
+
             _ => {}
         }
     }
 
     fn about_to_wait(&mut self, _: &ActiveEventLoop) {
         self.window
+            .as_ref()
             .expect("Bug - Window should exist")
             .request_redraw();
     }
 }
 
 impl VideoInput {
+    fn keyboard_input(&mut self, event: KeyEvent) {
+        if let PhysicalKey::Code(code) = event.physical_key
+            && let Some(c) = key_char(code)
+        {
+            let pressed = event.state == ElementState::Pressed;
+            if let Some(key) = self.keymap.key(c) {
+                self.send(Command::Key(key, pressed));
+                return;
+            }
+            if let Some(key) = self.keymap2.key(c) {
+                self.send(Command::Key2(key, pressed));
+                return;
+            }
+        }
+
+        if event.physical_key == PhysicalKey::Code(KEY_TURBO) {
+            let turbo = event.state == ElementState::Pressed;
+            if turbo != self.turbo {
+                self.turbo = turbo;
+                self.send(Command::Turbo(turbo));
+                self.update_title();
+            }
+            return;
+        }
+
+        if event.state != ElementState::Pressed {
+            return;
+        }
+
+        // Only the speed keys auto-repeat.
+        match event.physical_key {
+            PhysicalKey::Code(KEY_SLOWER | KeyCode::NumpadSubtract) => {
+                self.set_speed(slower(self.speed));
+            }
+            PhysicalKey::Code(KEY_FASTER | KeyCode::NumpadAdd) => {
+                self.set_speed(faster(self.speed));
+            }
+            _ => {}
+        }
+
+        if event.repeat {
+            return;
+        }
+
+        match event.physical_key {
+            PhysicalKey::Code(KEY_BROWSER) => {
+                self.open_browser();
+            }
+            PhysicalKey::Code(KEY_CYCLE_PALETTE) => {
+                self.palette_idx = (self.palette_idx + 1) % self.palettes.len();
+                if let Some(pixels) = &mut self.pixels {
+                    pixels.clear_color(clear_color(&self.palettes[self.palette_idx]));
+                }
+                self.update_title();
+            }
+            PhysicalKey::Code(KEY_CYCLE_FILTER) => {
+                self.filter.cycle();
+                self.update_title();
+            }
+            PhysicalKey::Code(KEY_RESET) => {
+                self.send(Command::Reset);
+                self.halted = false;
+                self.update_title();
+            }
+            PhysicalKey::Code(KEY_FRAME_ADVANCE) if self.paused => {
+                self.send(Command::Step);
+            }
+            PhysicalKey::Code(KEY_PAUSE | KeyCode::Pause) => {
+                self.toggle_pause();
+            }
+            PhysicalKey::Code(KEY_FULLSCREEN) => {
+                self.toggle_fullscreen();
+            }
+            _ => {}
+        }
+    }
+
+    fn browser_input(&mut self, event: KeyEvent) {
+        if event.state != ElementState::Pressed {
+            return;
+        }
+
+        match event.physical_key {
+            PhysicalKey::Code(KeyCode::ArrowUp) => self.browser.up(),
+            PhysicalKey::Code(KeyCode::ArrowDown) => self.browser.down(),
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => {
+                if let Some(rom) = self.browser.selected() {
+                    self.load_rom(rom.to_path_buf());
+                }
+            }
+            PhysicalKey::Code(KeyCode::Escape | KEY_BROWSER) => self.browsing = false,
+            _ => {}
+        }
+    }
+
+    /// Starts a ROM in place of the running one.
+    fn load_rom(&mut self, rom: PathBuf) {
+        self.send(Command::LoadRom(rom));
+        if self.paused {
+            self.toggle_pause();
+        }
+        self.browsing = false;
+        self.halted = false;
+        self.update_title();
+    }
+
+    fn send(&self, cmd: Command) {
+        // The CPU thread is gone only when it panicked, the window keeps the last frame.
+        let _ = self.commands.send(cmd);
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.send(if self.paused { Command::Pause } else { Command::Resume });
+        self.update_title();
+    }
+
+    fn set_speed(&mut self, ipf: u32) {
+        if ipf != self.speed {
+            self.speed = ipf;
+            self.send(Command::SetSpeed(ipf));
+            self.update_title();
+        }
+    }
+
+    /// The speed, the palette, the filter when there is one, and the run state.
+    fn title(&self) -> String {
+        let mut title = format!("0xID8 - {} ipf", self.speed);
+
+        if self.turbo {
+            title.push_str(" (turbo)");
+        }
+        title.push_str(&format!(" - {}", self.palettes[self.palette_idx].name));
+        if let Some(filter) = self.filter.mode().to_possible_value()
+            && self.filter.mode() != FilterMode::Off
+        {
+            title.push_str(&format!(" - {}", filter.get_name()));
+        }
+        if self.halted {
+            title.push_str(" - halted");
+        } else if self.paused {
+            title.push_str(" - paused");
+        }
+        title
+    }
+
+    fn update_title(&self) {
+        if let Some(window) = &self.window {
+            window.set_title(&self.title());
+        }
+    }
+
+    fn toggle_fullscreen(&self) {
+        if let Some(window) = &self.window {
+            if window.fullscreen().is_some() {
+                window.set_fullscreen(None);
+            } else {
+                window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+            }
+        }
+    }
+
+    /// Resizes the pixel buffer when the emulated resolution changes (lo-res / hi-res).
+    /// The window keeps its size, the renderer picks the new integer scale.
+    fn fit_buffer(&mut self, width: u32, height: u32) {
+        if self.buffer_size == (width, height) {
+            return;
+        }
+
+        if let Some(pixels) = &mut self.pixels {
+            pixels.resize_buffer(width, height).unwrap();
+        }
+
+        if let Some(window) = &self.window {
+            window.set_min_inner_size(Some(LogicalSize::new(width, height)));
+        }
+
+        self.buffer_size = (width, height);
+    }
+
     fn redraw_requested(&mut self) {
-        if let Some(rx) = &self.rx {
-            while let Ok(new_vram) = rx.try_recv() {
-                self.vram = new_vram;
+        while let Ok(status) = self.status.try_recv() {
+            match status {
+                Status::Frame(vram) => self.vram = vram,
+                Status::TrueColour(rgba) => self.true_colour = rgba,
+                Status::Error(e) => eprintln!("{e}"),
+                Status::Halted => {
+                    self.halted = true;
+                    self.update_title();
+                }
+                Status::Speed(ipf) => {
+                    self.speed = ipf;
+                    self.update_title();
+                }
+                Status::Colours(colours) => self.colours = colours.map(|c| *c),
+                Status::Resolution(width, height) => self.resolution = (width as u32, height as u32),
             }
         }
 
+        if self.browsing {
+            self.fit_buffer(BROWSER_W, BROWSER_H);
+        } else {
+            let (width, height) = self.resolution;
+            self.fit_buffer(width, height);
+        }
+
         if let Some(pixels) = &mut self.pixels {
             let frame = pixels.frame_mut();
+            let palette = &self.palettes[self.palette_idx];
 
-            for (i, value) in frame.chunks_exact_mut(4).enumerate() {
-                if self.vram[i] != 0u8 {
-                    value.copy_from_slice(&BIT_ON);
+            if self.browsing {
+                self.browser.draw(frame, palette);
+                pixels.render().unwrap();
+                self.send(Command::Tick(Sync::Vsync));
+                return;
+            }
+
+            if let Some(rgba) = &self.true_colour {
+                if frame.len() == rgba.len() {
+                    frame.copy_from_slice(rgba);
                 } else {
-                    value.copy_from_slice(&BIT_OFF);
+                    // The resolution changed under the MegaChip display.
+                    frame.fill(0);
                 }
+            } else if frame.len() == self.vram.width() * self.vram.height() * 4 {
+                let width = self.vram.width();
+                for (i, (value, pixel)) in frame.chunks_exact_mut(4).zip(self.vram.pixels()).enumerate() {
+                    let color = match &self.colours {
+                        Some(colours) => colours.color(i % width, i / width, pixel != 0),
+                        None => palette.color(pixel),
+                    };
+                    value.copy_from_slice(color);
+                }
+
+                self.filter.apply(&self.vram, frame);
+            } else {
+                // Between a resolution change and the first frame drawn at it.
+                frame.fill(0);
+            }
+
+            if self.paused {
+                draw_pause_icon(frame, self.buffer_size.0 as usize, palette);
             }
 
             pixels.render().unwrap();
+            // The surface presents with vsync, so the CPU thread can pace itself on this.
+            self.send(Command::Tick(Sync::Vsync));
         }
 
         if let Some(window) = &self.window {
@@ -113,3 +449,62 @@ impl VideoInput {
         }
     }
 }
+
+/// Draws a small "||" pause sign on a background box in the top right corner of the frame.
+fn draw_pause_icon(frame: &mut [u8], width: usize, palette: &Palette) {
+    const ICON: [&str; 7] = [
+        ".......",
+        ".##.##.",
+        ".##.##.",
+        ".##.##.",
+        ".##.##.",
+        ".##.##.",
+        ".......",
+    ];
+    let left = width - ICON[0].len() - 1;
+
+    for (y, row) in ICON.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let color = palette.color(if c == '#' { 1 } else { 0 });
+            let i = ((y + 1) * width + left + x) * 4;
+            frame[i..i + 4].copy_from_slice(color);
+        }
+    }
+}
+
+/// The letterbox colour, the palette background converted to linear RGB.
+fn clear_color(palette: &Palette) -> wgpu::Color {
+    let linear = |c: u8| {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let [r, g, b, _] = *palette.color(0);
+
+    wgpu::Color { r: linear(r), g: linear(g), b: linear(b), a: 1.0 }
+}
+
+/// The character of a letter, digit, comma or period key, used to look up the keypad mappings.
+fn key_char(code: KeyCode) -> Option<char> {
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+        KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF,
+        KeyCode::KeyG, KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL,
+        KeyCode::KeyM, KeyCode::KeyN, KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR,
+        KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU, KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX,
+        KeyCode::KeyY, KeyCode::KeyZ,
+    ];
+
+    if let Some(d) = DIGITS.iter().position(|&k| k == code) {
+        return char::from_digit(d as u32, 10);
+    }
+    match code {
+        KeyCode::Comma => return Some(','),
+        KeyCode::Period => return Some('.'),
+        _ => {}
+    }
+
+    LETTERS.iter().position(|&k| k == code).map(|l| (b'a' + l as u8) as char)
+}
@@ -0,0 +1,299 @@
+//! The COSMAC VIP itself, for `--vip`: a CDP1802, 4 KiB of RAM, the monitor ROM, the CDP1861
+//! "Pixie" video chip, the hex keypad and the speaker on Q. It boots the original CHIP-8
+//! interpreter at 0000 with the program at 0200, so the timing, the quirks and the 0nnn
+//! machine code calls are the ones of the real machine.
+//!
+//! The monitor ROM and the interpreter are RCA's, they are not shipped: their dumps are read
+//! from the files given with `--vip-monitor` and `--vip-interpreter`.
+
+use crate::cdp1802::{Bus, Cdp1802, CLOCKS_PER_CYCLE};
+use crate::config::{H, W};
+use crate::frame_buffer::FrameBuffer;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+const RAM_SIZE: usize = 4096;
+const ROM_SIZE: usize = 512;
+/// The interpreter takes the RAM below the programs.
+const PROGRAM_ADDRESS: usize = 0x200;
+
+/// The 1.76 MHz clock of the VIP.
+const CLOCK: u32 = 1_760_640;
+/// The CDP1861 frame: 262 lines of 14 machine cycles, 60 Hz.
+const LINES: u32 = 262;
+const CYCLES_PER_LINE: i32 = (CLOCK / CLOCKS_PER_CYCLE / 60 / LINES) as i32;
+/// The 128 lines shown, fetched by 8 DMA cycles each: 64 pixels.
+const PIXIE_LINES: usize = 128;
+const DISPLAY_LINES: std::ops::Range<u32> = 80..80 + PIXIE_LINES as u32;
+const DMA_PER_LINE: usize = 8;
+/// INT is asserted 2 lines before the display, EF1 4 lines before it starts and ends.
+const INT_LINES: std::ops::Range<u32> = 78..80;
+const EF1_LINES: [std::ops::Range<u32>; 2] = [76..80, 204..208];
+
+/// The monitor ROM and the CHIP-8 interpreter.
+pub struct Firmware {
+    monitor: Vec<u8>,
+    interpreter: Vec<u8>,
+}
+
+impl Firmware {
+    pub fn load(monitor: &Path, interpreter: &Path) -> Result<Self> {
+        let read = |path: &Path, what: &str| -> Result<Vec<u8>> {
+            let data = fs::read(path).with_context(|| format!("Cannot read the VIP {what} {}", path.display()))?;
+            if data.len() > ROM_SIZE {
+                bail!("The VIP {what} {} is {} bytes, more than {ROM_SIZE}", path.display(), data.len());
+            }
+            Ok(data)
+        };
+
+        Ok(Self { monitor: read(monitor, "monitor ROM")?, interpreter: read(interpreter, "interpreter")? })
+    }
+}
+
+/// Memory, video and keypad, as the 1802 sees them.
+struct VipBus {
+    ram: [u8; RAM_SIZE],
+    rom: [u8; ROM_SIZE],
+    /// After a reset the ROM shows at 0000 too, until the CPU reads above 8000.
+    rom_at_zero: bool,
+    /// Switched on by INP 1 and off by OUT 1.
+    display_on: bool,
+    /// The key EF3 reports, latched by OUT 2.
+    key_latch: u8,
+    keypad: [bool; 16],
+}
+
+impl Bus for VipBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        let addr = usize::from(addr);
+        if addr >= 0x8000 {
+            self.rom_at_zero = false;
+            self.rom[addr % ROM_SIZE]
+        } else if self.rom_at_zero {
+            self.rom[addr % ROM_SIZE]
+        } else {
+            // The RAM shows again every 4 KiB.
+            self.ram[addr % RAM_SIZE]
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            self.ram[usize::from(addr) % RAM_SIZE] = value;
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        0
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+}
+
+pub struct Vip {
+    cpu: Cdp1802,
+    bus: VipBus,
+    /// The lines the Pixie fetched this frame.
+    lines: [[u8; DMA_PER_LINE]; PIXIE_LINES],
+    /// Machine cycles run ahead of the line, the instructions do not end on its boundary.
+    cycles: i32,
+}
+
+impl Vip {
+    pub fn new(firmware: &Firmware) -> Self {
+        let mut rom = [0; ROM_SIZE];
+        rom[..firmware.monitor.len()].copy_from_slice(&firmware.monitor);
+        let mut ram = [0; RAM_SIZE];
+        ram[..firmware.interpreter.len()].copy_from_slice(&firmware.interpreter);
+
+        Self {
+            cpu: Cdp1802::new(),
+            bus: VipBus { ram, rom, rom_at_zero: true, display_on: false, key_latch: 0, keypad: [false; 16] },
+            lines: [[0; DMA_PER_LINE]; PIXIE_LINES],
+            cycles: 0,
+        }
+    }
+
+    /// Powers the machine on with `program` at 0200. The interpreter stays, the rest of the RAM
+    /// is cleared.
+    pub fn boot(&mut self, program: &[u8]) -> Result<()> {
+        if PROGRAM_ADDRESS + program.len() > RAM_SIZE {
+            bail!("the program does not fit in the {RAM_SIZE} bytes of the VIP");
+        }
+
+        self.bus.ram[PROGRAM_ADDRESS..].fill(0);
+        self.bus.ram[PROGRAM_ADDRESS..PROGRAM_ADDRESS + program.len()].copy_from_slice(program);
+        self.bus.rom_at_zero = true;
+        self.bus.display_on = false;
+        self.cpu = Cdp1802::new();
+        self.lines = [[0; DMA_PER_LINE]; PIXIE_LINES];
+        self.cycles = 0;
+        Ok(())
+    }
+
+    pub fn set_keypad(&mut self, keypad: [bool; 16]) {
+        self.bus.keypad = keypad;
+    }
+
+    /// The speaker sounds while Q is set.
+    pub fn q(&self) -> bool {
+        self.cpu.q
+    }
+
+    /// Runs the 262 lines of a frame, returns the number of instructions run.
+    pub fn run_frame(&mut self) -> u32 {
+        let mut instructions = 0;
+
+        for line in 0..LINES {
+            let on = self.bus.display_on;
+            let dma = on && DISPLAY_LINES.contains(&line);
+            self.cpu.ef[0] = on && EF1_LINES.iter().any(|lines| lines.contains(&line));
+            self.cpu.int = on && INT_LINES.contains(&line);
+
+            self.cycles += CYCLES_PER_LINE - if dma { DMA_PER_LINE as i32 } else { 0 };
+            while self.cycles > 0 {
+                self.cpu.ef[2] = self.bus.keypad[usize::from(self.bus.key_latch)];
+                self.cycles -= self.cpu.step(&mut self.bus) as i32;
+                instructions += 1;
+            }
+
+            if dma {
+                let fetched = &mut self.lines[(line - DISPLAY_LINES.start) as usize];
+                for byte in fetched.iter_mut() {
+                    *byte = self.cpu.dma_out(&mut self.bus);
+                }
+            }
+        }
+
+        if !self.bus.display_on {
+            self.lines = [[0; DMA_PER_LINE]; PIXIE_LINES];
+        }
+        instructions
+    }
+
+    /// Draws the 128 lines of the Pixie on `vram`, 4 to a row: the interpreter repeats every
+    /// row 4 times.
+    pub fn draw(&self, vram: &mut FrameBuffer) {
+        let repeat = self.lines.len() / H;
+
+        for (y, rows) in self.lines.chunks(repeat).enumerate() {
+            for x in 0..W {
+                let lit = rows.iter().any(|line| line[x / 8] & (0x80 >> (x % 8)) != 0);
+                vram.set(x, y, u8::from(lit));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vip(monitor: &[u8]) -> Vip {
+        Vip::new(&Firmware { monitor: monitor.to_vec(), interpreter: Vec::new() })
+    }
+
+    /// Sets the display on and points R0 at 0200 on every display interrupt.
+    const DISPLAY: [u8; 42] = [
+        0xF8, 0x80, // 00 LDI 80
+        0xB1,       // 02 PHI 1
+        0xB3,       // 03 PHI 3
+        0xF8, 0x20, // 04 LDI 20
+        0xA1,       // 06 PLO 1     R1 = 8020, the interrupt routine
+        0xF8, 0x18, // 07 LDI 18
+        0xA3,       // 09 PLO 3     R3 = 8018
+        0xF8, 0x0F, // 0A LDI 0F
+        0xB2,       // 0C PHI 2
+        0xF8, 0xFF, // 0D LDI FF
+        0xA2,       // 0F PLO 2     R2 = 0FFF, the stack
+        0xE2,       // 10 SEX 2
+        0x69,       // 11 INP 1     display on
+        0xD3,       // 12 SEP 3     run from 8018, above the ROM mirror
+        0x00, 0x00, 0x00, 0x00, 0x00,
+        0x30, 0x18, // 18 BR 18
+        0x00, 0x00, 0x00, 0x00, 0x00,
+        0x70,       // 1F RET       R1 is back at the routine
+        0x22,       // 20 DEC 2
+        0x78,       // 21 SAV
+        0xF8, 0x02, // 22 LDI 02
+        0xB0,       // 24 PHI 0
+        0xF8, 0x00, // 25 LDI 00
+        0xA0,       // 27 PLO 0     R0 = 0200
+        0x30, 0x1F, // 28 BR 1F
+    ];
+
+    #[test]
+    fn the_pixie_shows_the_memory_r0_points_to() {
+        let mut vip = vip(&DISPLAY);
+        let mut program = [0; 1024];
+        program[0] = 0x80;
+        program[124 * 8 + 7] = 0x01;
+        vip.boot(&program).unwrap();
+
+        vip.run_frame();
+        vip.run_frame();
+
+        let mut vram = FrameBuffer::new(W, H, 1);
+        vip.draw(&mut vram);
+        assert_eq!(vram.get(0, 0), 1);
+        assert_eq!(vram.get(1, 0), 0);
+        assert_eq!(vram.get(W - 1, H - 1), 1);
+        assert_eq!(vram.pixels().filter(|&p| p != 0).count(), 2);
+    }
+
+    #[test]
+    fn ef3_reports_the_latched_key() {
+        let monitor = [
+            0xF8, 0x80, 0xB3, // R3.1 = 80
+            0xF8, 0x07, 0xA3, // R3.0 = 07
+            0xD3,             // SEP 3, to 8007: the RAM shows at 0000
+            0xF8, 0x0F, 0xB2, // R2.1 = 0F
+            0xF8, 0xF0, 0xA2, // R2.0 = F0
+            0xE2,             // SEX 2
+            0xF8, 0x05, 0x52, // M(R2) = 5
+            0x62,             // OUT 2, latch key 5
+            0x36, 0x15,       // B3 15
+            0x00,             // IDL
+            0x7B,             // 15 SEQ
+            0x00,             // IDL
+        ];
+
+        for pressed in [false, true] {
+            let mut vip = vip(&monitor);
+            vip.boot(&[]).unwrap();
+            let mut keypad = [false; 16];
+            keypad[5] = pressed;
+            vip.set_keypad(keypad);
+
+            vip.run_frame();
+            assert_eq!(vip.q(), pressed);
+        }
+    }
+
+    #[test]
+    fn the_rom_shows_at_zero_until_the_cpu_reads_above_8000() {
+        let mut vip = vip(&[0x12]);
+        vip.boot(&[0x34]).unwrap();
+
+        assert_eq!(vip.bus.read(0x0000), 0x12);
+        assert_eq!(vip.bus.read(0x8000), 0x12);
+        assert_eq!(vip.bus.read(0x0200), 0x34);
+        assert_eq!(vip.bus.read(0x1200), 0x34);
+    }
+
+    #[test]
+    fn boot_rejects_programs_larger_than_the_ram() {
+        let mut vip = vip(&[]);
+        assert!(vip.boot(&[0; RAM_SIZE]).is_err());
+    }
+}